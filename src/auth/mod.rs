@@ -1,5 +1,4 @@
 use anyhow::Result;
-use base64::{engine::general_purpose::STANDARD, Engine};
 use oauth2::{
     basic::BasicClient, AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken,
     PkceCodeChallenge, RedirectUrl, RefreshToken, Scope, TokenResponse, TokenUrl,
@@ -7,7 +6,7 @@ use oauth2::{
 use std::io::{BufRead, BufReader, Write};
 use std::net::TcpListener;
 
-use crate::config::Config;
+use crate::config::AccountConfig;
 
 const GOOGLE_AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
 const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
@@ -17,7 +16,10 @@ pub struct GoogleAuth {
 }
 
 impl GoogleAuth {
-    pub fn new(config: &Config) -> Result<Self> {
+    pub fn new(config: &AccountConfig) -> Result<Self> {
+        if config.client_id.is_empty() {
+            anyhow::bail!("client_id is required for XOAUTH2 ({})", config.email);
+        }
         let client = BasicClient::new(
             ClientId::new(config.client_id.clone()),
             Some(ClientSecret::new(config.client_secret.clone())),
@@ -0,0 +1,107 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::email::Email;
+
+/// Messages of one folder kept on disk, so the list can be shown before
+/// (or without) a connection and bodies are only downloaded once.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FolderCache {
+    /// UIDs are only meaningful within one UIDVALIDITY
+    pub uid_validity: u32,
+    /// HIGHESTMODSEQ the cached flags are current as of, 0 when unknown
+    #[serde(default)]
+    pub highest_modseq: u64,
+    emails: BTreeMap<u32, Email>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl FolderCache {
+    fn path(account: &str, folder: &str) -> Result<PathBuf> {
+        let data_dir = dirs::data_dir()
+            .ok_or_else(|| anyhow::anyhow!("Could not find data directory"))?;
+        let cache_dir = data_dir
+            .join("rustmail")
+            .join("cache")
+            .join(file_name(account));
+        fs::create_dir_all(&cache_dir)?;
+        Ok(cache_dir.join(format!("{}.json", file_name(folder))))
+    }
+
+    /// Load the cache for `folder` of the account with address `account`.
+    /// A missing or unreadable cache is simply empty.
+    pub fn load(account: &str, folder: &str) -> Self {
+        let path = Self::path(account, folder).ok();
+        let mut cache: Self = path
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        cache.path = path;
+        cache
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        // Write then rename, so a crash never leaves half a file behind
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string(self)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// Drop everything if the server renumbered the folder
+    pub fn validate(&mut self, uid_validity: u32) {
+        if self.uid_validity != uid_validity {
+            self.emails.clear();
+            self.uid_validity = uid_validity;
+            self.highest_modseq = 0;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.emails.is_empty()
+    }
+
+    pub fn uids(&self) -> Vec<u32> {
+        self.emails.keys().copied().collect()
+    }
+
+    pub fn contains(&self, uid: u32) -> bool {
+        self.emails.contains_key(&uid)
+    }
+
+    pub fn get_mut(&mut self, uid: u32) -> Option<&mut Email> {
+        self.emails.get_mut(&uid)
+    }
+
+    pub fn insert(&mut self, email: Email) {
+        self.emails.insert(email.uid, email);
+    }
+
+    pub fn remove(&mut self, uid: u32) {
+        self.emails.remove(&uid);
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(u32) -> bool) {
+        self.emails.retain(|uid, _| keep(*uid));
+    }
+
+    /// The `count` highest UIDs, newest first
+    pub fn newest(&self, count: usize) -> Vec<Email> {
+        self.emails.values().rev().take(count).cloned().collect()
+    }
+}
+
+/// Make an account address or folder name safe to use as a file name
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_alphanumeric() || c == '@' || c == '.' || c == '-' { c } else { '_' })
+        .collect()
+}
@@ -1,17 +1,223 @@
 use anyhow::Result;
+use chrono::NaiveTime;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsMode {
+    /// TLS from the first byte (IMAPS on 993, SMTPS on 465)
+    #[default]
+    Implicit,
+    /// Plain connection upgraded with STARTTLS (IMAP on 143, submission on 587)
+    Starttls,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMethod {
+    #[default]
+    Xoauth2,
+    Plain,
+    Login,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Config {
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub tls: TlsMode,
+}
+
+impl ServerConfig {
+    fn gmail_imap() -> Self {
+        Self {
+            host: "imap.gmail.com".to_string(),
+            port: 993,
+            tls: TlsMode::Implicit,
+        }
+    }
+
+    fn gmail_smtp() -> Self {
+        Self {
+            host: "smtp.gmail.com".to_string(),
+            port: 465,
+            tls: TlsMode::Implicit,
+        }
+    }
+}
+
+/// Folders to use instead of the ones the server marks (RFC 6154), by
+/// their IMAP name
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FolderOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drafts: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub junk: Option<String>,
+}
+
+impl FolderOverrides {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountConfig {
+    /// Short name used by the account switcher, defaults to `email`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
     pub email: String,
+    #[serde(default)]
     pub client_id: String,
+    #[serde(default)]
     pub client_secret: String,
     #[serde(default)]
     pub access_token: Option<String>,
     #[serde(default)]
     pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub auth: AuthMethod,
+    /// Login name for PLAIN/LOGIN, defaults to `email`
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Skip certificate validation, for local test servers with self-signed certs
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+    #[serde(default = "ServerConfig::gmail_imap")]
+    pub imap: ServerConfig,
+    #[serde(default = "ServerConfig::gmail_smtp")]
+    pub smtp: ServerConfig,
+    #[serde(default, skip_serializing_if = "FolderOverrides::is_empty")]
+    pub folders: FolderOverrides,
+}
+
+impl AccountConfig {
+    pub fn display_name(&self) -> &str {
+        if self.name.is_empty() {
+            &self.email
+        } else {
+            &self.name
+        }
+    }
+
+    pub fn username(&self) -> &str {
+        self.username.as_deref().unwrap_or(&self.email)
+    }
+
+    /// The secret handed to the server: the OAuth access token for XOAUTH2,
+    /// the password otherwise.
+    pub fn secret(&self) -> Result<&str> {
+        match self.auth {
+            AuthMethod::Xoauth2 => self
+                .access_token
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("No access token for {}", self.email)),
+            AuthMethod::Plain | AuthMethod::Login => self
+                .password
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("No password configured for {}", self.email)),
+        }
+    }
+}
+
+fn default_page_size() -> u32 {
+    50
+}
+
+fn default_undo_send() -> u64 {
+    5
+}
+
+/// What the vaguer reminder times stand for, as "HH:MM"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnoozeConfig {
+    /// Days without a time: "tomorrow", "monday", "next week", …
+    pub morning: String,
+    pub afternoon: String,
+    /// "tonight" and "evening"
+    pub tonight: String,
+}
+
+impl Default for SnoozeConfig {
+    fn default() -> Self {
+        Self {
+            morning: "08:00".to_string(),
+            afternoon: "13:00".to_string(),
+            tonight: "20:00".to_string(),
+        }
+    }
+}
+
+impl SnoozeConfig {
+    pub fn morning(&self) -> NaiveTime {
+        parse_clock(&self.morning).unwrap_or_default()
+    }
+
+    pub fn afternoon(&self) -> NaiveTime {
+        parse_clock(&self.afternoon).unwrap_or_default()
+    }
+
+    pub fn tonight(&self) -> NaiveTime {
+        parse_clock(&self.tonight).unwrap_or_default()
+    }
+
+    fn validate(&self) -> Result<()> {
+        for (name, value) in [
+            ("morning", &self.morning),
+            ("afternoon", &self.afternoon),
+            ("tonight", &self.tonight),
+        ] {
+            if parse_clock(value).is_none() {
+                anyhow::bail!("snooze.{} must be a time like \"08:00\", not {:?}", name, value);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_clock(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M").ok()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    /// How many messages a folder listing fetches at a time
+    #[serde(default = "default_page_size")]
+    pub page_size: u32,
+    #[serde(default)]
+    pub snooze: SnoozeConfig,
+    /// Seconds a sent message is held back and can still be undone, 0 to
+    /// send at once
+    #[serde(default = "default_undo_send")]
+    pub undo_send: u64,
+    pub accounts: Vec<AccountConfig>,
+}
+
+/// A config from before there were several accounts, with its one
+/// account at the top level
+#[derive(Deserialize)]
+struct LegacyConfig {
+    #[serde(default = "default_page_size")]
+    page_size: u32,
+    #[serde(default)]
+    snooze: SnoozeConfig,
+    #[serde(default = "default_undo_send")]
+    undo_send: u64,
+    #[serde(flatten)]
+    account: AccountConfig,
 }
 
 impl Config {
@@ -36,7 +242,30 @@ impl Config {
             );
         }
         let content = fs::read_to_string(&path)?;
-        let config: Config = toml::from_str(&content)?;
+        Self::parse(&content).map_err(|e| anyhow::anyhow!("{} in {:?}", e, path))
+    }
+
+    fn parse(content: &str) -> Result<Self> {
+        let value: toml::Table = toml::from_str(content)?;
+        // Older configs describe a single account at the top level
+        let config = if value.contains_key("accounts") {
+            value.try_into::<Config>()?
+        } else {
+            let legacy = value.try_into::<LegacyConfig>()?;
+            Config {
+                page_size: legacy.page_size,
+                snooze: legacy.snooze,
+                undo_send: legacy.undo_send,
+                accounts: vec![legacy.account],
+            }
+        };
+        if config.page_size == 0 {
+            anyhow::bail!("page_size must be at least 1");
+        }
+        if config.accounts.is_empty() {
+            anyhow::bail!("No accounts configured");
+        }
+        config.snooze.validate()?;
         Ok(config)
     }
 
@@ -47,3 +276,50 @@ impl Config {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_single_account() {
+        let config = Config::parse(
+            r#"
+email = "me@example.com"
+page_size = 20
+undo_send = 0
+
+[imap]
+host = "imap.example.com"
+port = 143
+tls = "starttls"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.page_size, 20);
+        assert_eq!(config.undo_send, 0);
+        assert_eq!(config.accounts.len(), 1);
+        assert_eq!(config.accounts[0].email, "me@example.com");
+        assert_eq!(config.accounts[0].imap.port, 143);
+        assert_eq!(config.accounts[0].imap.tls, TlsMode::Starttls);
+    }
+
+    #[test]
+    fn legacy_defaults() {
+        let config = Config::parse(r#"email = "me@example.com""#).unwrap();
+        assert_eq!(config.page_size, default_page_size());
+        assert_eq!(config.undo_send, default_undo_send());
+        assert_eq!(config.accounts[0].imap.host, "imap.gmail.com");
+    }
+
+    #[test]
+    fn page_size_must_be_positive() {
+        for page_size in ["0", "-1", "-4294967295"] {
+            let legacy = format!("email = \"me@example.com\"\npage_size = {}", page_size);
+            assert!(Config::parse(&legacy).is_err(), "legacy page_size = {}", page_size);
+            let accounts = format!("page_size = {}\n[[accounts]]\nemail = \"me@example.com\"", page_size);
+            assert!(Config::parse(&accounts).is_err(), "page_size = {}", page_size);
+        }
+        assert!(Config::parse("email = \"me@example.com\"\nundo_send = -5").is_err());
+    }
+}
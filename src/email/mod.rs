@@ -1,12 +1,27 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use html2text::from_read;
+use imap::types::NameAttribute;
 use imap::{Authenticator, Session};
+use imap_proto::types::{BodyStructure, MessageSection, SectionPath};
 use mail_parser::MessageParser;
-use native_tls::TlsStream;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::net::TcpStream;
+use std::time::Duration;
+
+mod parse;
+mod stream;
+mod thread;
+pub mod utf7;
+
+use parse::{quote, GmailAttrs, SelectResponse};
+use stream::SharedStream;
+pub use thread::conversations;
 
 use crate::auth::build_oauth2_string;
+use crate::cache::FolderCache;
+use crate::config::{AccountConfig, AuthMethod, FolderOverrides, TlsMode};
 
 fn html_to_text(html: &str) -> String {
     let text = from_read(html.as_bytes(), 80);
@@ -20,6 +35,211 @@ fn html_to_text(html: &str) -> String {
         .join("\n")
 }
 
+/// The folder for each special use in `listing`. Configured folders come
+/// first, then the attributes, then common names. Where nothing is marked
+/// \Archive, as on Gmail, archiving goes to All Mail.
+fn resolve_special(listing: &[imap::types::Name], overrides: &FolderOverrides) -> HashMap<SpecialUse, String> {
+    let mut special = HashMap::new();
+    for use_ in SpecialUse::ALL {
+        let marked = listing.iter().find(|folder| {
+            folder
+                .attributes()
+                .iter()
+                .any(|a| matches!(a, NameAttribute::Custom(c) if c.eq_ignore_ascii_case(use_.attribute())))
+        });
+        let named = || {
+            use_.common_names().iter().find_map(|common| {
+                listing.iter().find(|folder| folder.name().eq_ignore_ascii_case(common))
+            })
+        };
+        let name = use_
+            .configured(overrides)
+            .cloned()
+            .or_else(|| marked.or_else(named).map(|folder| folder.name().to_string()));
+        if let Some(name) = name {
+            special.insert(use_, name);
+        }
+    }
+    if !special.contains_key(&SpecialUse::Archive) {
+        if let Some(all) = special.get(&SpecialUse::All).cloned() {
+            special.insert(SpecialUse::Archive, all);
+        }
+    }
+    special
+}
+
+fn is_seen(msg: &imap::types::Fetch) -> bool {
+    msg.flags().iter().any(|f| matches!(f, imap::types::Flag::Seen))
+}
+
+/// Starred, in Gmail's words
+fn is_flagged(msg: &imap::types::Fetch) -> bool {
+    msg.flags().iter().any(|f| matches!(f, imap::types::Flag::Flagged))
+}
+
+/// What the message list needs: flags, the headers shown or used for
+/// threading, and the structure to find the text part in later
+const LIST_QUERY: &str = "(UID FLAGS BODYSTRUCTURE \
+    BODY.PEEK[HEADER.FIELDS (FROM SUBJECT DATE MESSAGE-ID IN-REPLY-TO REFERENCES)])";
+
+/// Readable text of a parsed message or part, preferring HTML like Gmail does
+fn message_text(parsed: &mail_parser::Message) -> String {
+    if let Some(html) = parsed.body_html(0) {
+        html_to_text(&html)
+    } else if let Some(text) = parsed.body_text(0) {
+        text.to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Find the part to show in a BODYSTRUCTURE, HTML over plain text. Returns
+/// its section ("1.2"), None for single part messages, and whether any
+/// other part is an attachment.
+fn text_section(structure: &BodyStructure) -> (Option<String>, bool) {
+    fn walk(
+        part: &BodyStructure,
+        path: &mut Vec<u32>,
+        html: &mut Option<Vec<u32>>,
+        plain: &mut Option<Vec<u32>>,
+        attachments: &mut bool,
+    ) {
+        match part {
+            BodyStructure::Multipart { bodies, .. } => {
+                for (i, body) in bodies.iter().enumerate() {
+                    path.push(i as u32 + 1);
+                    walk(body, path, html, plain, attachments);
+                    path.pop();
+                }
+            }
+            BodyStructure::Text { common, .. } => {
+                let attached = common
+                    .disposition
+                    .as_ref()
+                    .is_some_and(|d| d.ty.eq_ignore_ascii_case("attachment"));
+                if attached {
+                    *attachments = true;
+                } else if common.ty.subtype.eq_ignore_ascii_case("html") {
+                    html.get_or_insert_with(|| path.clone());
+                } else if common.ty.subtype.eq_ignore_ascii_case("plain") {
+                    plain.get_or_insert_with(|| path.clone());
+                }
+            }
+            BodyStructure::Basic { common, .. } | BodyStructure::Message { common, .. } => {
+                let inline = common
+                    .disposition
+                    .as_ref()
+                    .is_some_and(|d| d.ty.eq_ignore_ascii_case("inline"));
+                if !inline {
+                    *attachments = true;
+                }
+            }
+        }
+    }
+
+    let (mut html, mut plain, mut attachments) = (None, None, false);
+    walk(structure, &mut Vec::new(), &mut html, &mut plain, &mut attachments);
+    let section = html
+        .or(plain)
+        .filter(|path| !path.is_empty())
+        .map(|path| path.iter().map(|p| p.to_string()).collect::<Vec<_>>().join("."));
+    (section, attachments)
+}
+
+/// Turn a fetch into an `Email`. With `BODY.PEEK[]` the body is read right
+/// away; with `LIST_QUERY` only the headers are, and the body is left for
+/// `fetch_body`. The account is left at 0 for the caller to fill in.
+fn parse_message(
+    parser: &MessageParser,
+    msg: &imap::types::Fetch,
+    folder: &str,
+    important: bool,
+) -> Option<Email> {
+    let uid = msg.uid?;
+    let body_loaded = msg.body().is_some();
+    let parsed = parser.parse(msg.body().or_else(|| msg.header())?)?;
+
+    let subject = parsed
+        .subject()
+        .unwrap_or("(No Subject)")
+        .to_string();
+
+    let from_addr = parsed
+        .from()
+        .and_then(|f| f.first());
+
+    let from = from_addr
+        .map(|a| {
+            a.name()
+                .map(|n| n.to_string())
+                .unwrap_or_else(|| a.address().unwrap_or("").to_string())
+        })
+        .unwrap_or_else(|| "(Unknown)".to_string());
+
+    let from_address = from_addr
+        .and_then(|a| a.address())
+        .unwrap_or("")
+        .to_string();
+
+    let date = parsed.date().map(|d| {
+        DateTime::from_timestamp(d.to_timestamp(), 0).unwrap_or_default()
+    });
+
+    let body_text = if body_loaded {
+        message_text(&parsed)
+    } else {
+        String::new()
+    };
+    let (body_section, has_attachments) = msg
+        .bodystructure()
+        .map(text_section)
+        .unwrap_or((None, false));
+
+    let seen = is_seen(msg);
+    let flagged = is_flagged(msg);
+
+    let message_id = parsed.message_id().map(|s| s.to_string());
+    let in_reply_to = parsed.in_reply_to()
+        .as_text_list()
+        .and_then(|v| v.first().map(|s| s.to_string()));
+    let references: Vec<String> = parsed
+        .references()
+        .as_text_list()
+        .map(|v| v.iter().map(|s| s.to_string()).collect())
+        .unwrap_or_default();
+
+    Some(Email {
+        account: 0,
+        folder: folder.to_string(),
+        uid,
+        subject,
+        from,
+        from_address,
+        date,
+        body: body_text,
+        body_loaded,
+        body_section,
+        has_attachments,
+        seen,
+        flagged,
+        important,
+        message_id,
+        in_reply_to,
+        references,
+        thread_id: None,
+        gmail_id: None,
+        labels: Vec::new(),
+    })
+}
+
+fn add_gmail_attrs(email: &mut Email, gmail: &HashMap<u32, GmailAttrs>) {
+    if let Some(attrs) = gmail.get(&email.uid) {
+        email.thread_id = attrs.thread_id;
+        email.gmail_id = attrs.message_id;
+        email.labels = attrs.labels.clone();
+    }
+}
+
 struct OAuth2Authenticator(String);
 
 impl Authenticator for OAuth2Authenticator {
@@ -29,54 +249,473 @@ impl Authenticator for OAuth2Authenticator {
     }
 }
 
-#[derive(Debug, Clone)]
+struct PlainAuthenticator(String);
+
+impl Authenticator for PlainAuthenticator {
+    type Response = String;
+    fn process(&self, _data: &[u8]) -> Self::Response {
+        self.0.clone()
+    }
+}
+
+/// Identifies a message across accounts and folders; UIDs alone collide
+/// between servers and between the folders of one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MessageKey {
+    pub account: usize,
+    pub folder: String,
+    pub uid: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Email {
+    /// Index into the configured accounts, which may change between runs
+    #[serde(skip)]
+    pub account: usize,
+    pub folder: String,
     pub uid: u32,
     pub subject: String,
     pub from: String,
     pub from_address: String,
     pub date: Option<DateTime<Utc>>,
+    /// Empty until downloaded, see `body_loaded`
     pub body: String,
+    #[serde(default)]
+    pub body_loaded: bool,
+    /// Section of the part holding the text, None to take the whole message
+    #[serde(default)]
+    pub body_section: Option<String>,
+    #[serde(default)]
+    pub has_attachments: bool,
     pub seen: bool,
+    /// \Flagged, shown as a star
+    #[serde(default)]
+    pub flagged: bool,
     pub important: bool,
     pub message_id: Option<String>,
     pub in_reply_to: Option<String>,
     pub references: Vec<String>,
+    /// Gmail's X-GM-THRID, None elsewhere
+    #[serde(default)]
+    pub thread_id: Option<u64>,
+    /// Gmail's X-GM-MSGID, which stays the same in every folder
+    #[serde(default)]
+    pub gmail_id: Option<u64>,
+    /// Gmail's X-GM-LABELS, in modified UTF-7
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+impl Email {
+    pub fn key(&self) -> MessageKey {
+        MessageKey {
+            account: self.account,
+            folder: self.folder.clone(),
+            uid: self.uid,
+        }
+    }
+}
+
+/// A message ready to be handed to SMTP or appended as a draft.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OutgoingEmail {
+    pub to: String,
+    pub cc: String,
+    pub subject: String,
+    pub body: String,
+    pub in_reply_to: Option<String>,
+    pub references: Vec<String>,
+    /// Set once sent, without the angle brackets
+    pub message_id: Option<String>,
+}
+
+/// What a folder is for, as RFC 6154 marks it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpecialUse {
+    Sent,
+    Drafts,
+    Trash,
+    Archive,
+    Junk,
+    /// Gmail's view of every \Flagged message
+    Flagged,
+    /// Gmail's All Mail
+    All,
+}
+
+impl SpecialUse {
+    /// In the order a folder with several is known by
+    const ALL: [SpecialUse; 7] = [
+        SpecialUse::Sent,
+        SpecialUse::Drafts,
+        SpecialUse::Trash,
+        SpecialUse::Archive,
+        SpecialUse::Junk,
+        SpecialUse::Flagged,
+        SpecialUse::All,
+    ];
+
+    fn attribute(self) -> &'static str {
+        match self {
+            SpecialUse::Sent => "\\Sent",
+            SpecialUse::Drafts => "\\Drafts",
+            SpecialUse::Trash => "\\Trash",
+            SpecialUse::Archive => "\\Archive",
+            SpecialUse::Junk => "\\Junk",
+            SpecialUse::Flagged => "\\Flagged",
+            SpecialUse::All => "\\All",
+        }
+    }
+
+    /// Names the folder often has on servers without SPECIAL-USE
+    fn common_names(self) -> &'static [&'static str] {
+        match self {
+            SpecialUse::Sent => &["Sent", "Sent Items", "Sent Messages", "INBOX.Sent"],
+            SpecialUse::Drafts => &["Drafts", "INBOX.Drafts"],
+            SpecialUse::Trash => &["Trash", "Deleted Items", "Deleted Messages", "INBOX.Trash"],
+            SpecialUse::Archive => &["Archive", "INBOX.Archive"],
+            SpecialUse::Junk => &["Junk", "Spam", "Junk E-mail", "INBOX.Junk", "INBOX.Spam"],
+            SpecialUse::Flagged | SpecialUse::All => &[],
+        }
+    }
+
+    fn configured(self, overrides: &FolderOverrides) -> Option<&String> {
+        match self {
+            SpecialUse::Sent => overrides.sent.as_ref(),
+            SpecialUse::Drafts => overrides.drafts.as_ref(),
+            SpecialUse::Trash => overrides.trash.as_ref(),
+            SpecialUse::Archive => overrides.archive.as_ref(),
+            SpecialUse::Junk => overrides.junk.as_ref(),
+            SpecialUse::Flagged | SpecialUse::All => None,
+        }
+    }
+}
+
+/// A folder, or Gmail label, as LIST reports it
+#[derive(Debug, Clone)]
+pub struct FolderInfo {
+    pub name: String,
+    /// Separates levels of the name, "/" on Gmail
+    pub delimiter: Option<String>,
+    pub unread: Option<u32>,
+    pub special: Option<SpecialUse>,
+}
+
+/// What finds a message again after it moved: its Message-ID and Gmail's
+/// message ID
+pub type MessageRef = (Option<String>, Option<u64>);
+
+/// What came of looking for a reminder's message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReminderOutcome {
+    /// Back in the inbox, unread
+    Resurfaced,
+    /// Someone answered the sent message, so there is nothing to remind of
+    Replied,
+    /// Nowhere to be found
+    Missing,
+}
+
+/// A UID set for commands, e.g. "4,8,15"
+fn uid_set(uids: &[u32]) -> String {
+    uids.iter().map(|u| u.to_string()).collect::<Vec<_>>().join(",")
+}
+
+/// Whether `err` means the connection is gone, rather than the server
+/// refusing one command. Parse errors count too, as the stream is out of
+/// step afterwards.
+pub fn is_disconnect(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<imap::Error>(),
+        Some(
+            imap::Error::Io(_)
+                | imap::Error::ConnectionLost
+                | imap::Error::Tls(_)
+                | imap::Error::Parse(_)
+        )
+    )
 }
 
 pub struct ImapClient {
-    session: Session<TlsStream<TcpStream>>,
+    session: Session<SharedStream>,
+    /// The session's own stream, for commands the `imap` crate cannot read
+    stream: SharedStream,
+    tag: u32,
+    selected: Option<String>,
+    /// Whether the server has Gmail's X-GM-EXT-1 attributes
+    gmail: bool,
+    /// Whether the server can expunge chosen messages with UID EXPUNGE
+    uidplus: bool,
+    /// Whether the server has MOVE (RFC 6851)
+    can_move: bool,
+    /// Folders configured in place of the special-use ones
+    overrides: FolderOverrides,
+    /// The folder for each special use, looked up on first use
+    special: Option<HashMap<SpecialUse, String>>,
 }
 
 impl ImapClient {
-    pub fn connect(email: &str, access_token: &str) -> Result<Self> {
-        let tls = native_tls::TlsConnector::builder().build()?;
-        let client = imap::connect(("imap.gmail.com", 993), "imap.gmail.com", &tls)?;
+    pub fn connect(config: &AccountConfig) -> Result<Self> {
+        let tls = native_tls::TlsConnector::builder()
+            .danger_accept_invalid_certs(config.accept_invalid_certs)
+            .build()?;
+        let server = &config.imap;
+        let tcp = TcpStream::connect((server.host.as_str(), server.port))?;
+        if server.tls == TlsMode::Starttls {
+            stream::starttls(&tcp)?;
+        }
+        let stream = SharedStream::new(
+            tls.connect(&server.host, tcp)
+                .map_err(|e| anyhow::anyhow!("TLS handshake with {} failed: {}", server.host, e))?,
+        );
+        let mut client = imap::Client::new(stream.clone());
+        if server.tls == TlsMode::Implicit {
+            client.read_greeting()?;
+        }
+
+        let user = config.username();
+        let secret = config.secret()?;
+        let session = match config.auth {
+            AuthMethod::Xoauth2 => {
+                let oauth2_token = build_oauth2_string(user, secret);
+                client.authenticate("XOAUTH2", &OAuth2Authenticator(oauth2_token))
+            }
+            AuthMethod::Plain => {
+                let plain = format!("\0{}\0{}", user, secret);
+                client.authenticate("PLAIN", &PlainAuthenticator(plain))
+            }
+            AuthMethod::Login => client.login(user, secret),
+        }
+        .map_err(|(e, _)| e)?;
 
-        let oauth2_token = build_oauth2_string(email, access_token);
-        let session = client
-            .authenticate("XOAUTH2", &OAuth2Authenticator(oauth2_token))
-            .map_err(|(e, _)| e)?;
+        let mut client = Self {
+            session,
+            stream,
+            tag: 0,
+            selected: None,
+            gmail: false,
+            uidplus: false,
+            can_move: false,
+            overrides: config.folders.clone(),
+            special: None,
+        };
+        if let Ok(caps) = client.session.capabilities() {
+            client.gmail = caps.has_str("X-GM-EXT-1");
+            client.uidplus = caps.has_str("UIDPLUS");
+            client.can_move = caps.has_str("MOVE");
+        }
+        Ok(client)
+    }
 
-        Ok(Self { session })
+    /// Run a command the `imap` crate cannot read the response of
+    fn raw_command(&mut self, command: &str) -> Result<Vec<u8>> {
+        self.tag += 1;
+        self.stream.run(&format!("x{}", self.tag), command)
     }
 
-    pub fn list_folders(&mut self) -> Result<Vec<String>> {
-        let folders = self.session.list(Some(""), Some("*"))?;
-        Ok(folders.iter().map(|f| f.name().to_string()).collect())
+    /// Gmail's thread and message IDs and labels of `uid_list` in the
+    /// selected folder, empty unless the server is Gmail
+    fn gmail_attrs(&mut self, uid_list: &str) -> Result<HashMap<u32, GmailAttrs>> {
+        if !self.gmail || uid_list.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let response = self.raw_command(&format!(
+            "UID FETCH {} (UID X-GM-THRID X-GM-MSGID X-GM-LABELS)",
+            uid_list
+        ))?;
+        Ok(parse::parse_gmail_fetch(&response)
+            .into_iter()
+            .map(|attrs| (attrs.uid, attrs))
+            .collect())
+    }
+
+    /// UIDs of the selected folder Gmail rates important, among `uid_list`
+    /// when given, empty unless the server is Gmail
+    fn important_uids(&mut self, uid_list: Option<&str>) -> std::collections::HashSet<u32> {
+        if !self.gmail {
+            return Default::default();
+        }
+        let query = match uid_list {
+            Some(uid_list) => format!("UID {} X-GM-RAW \"is:important\"", uid_list),
+            None => "X-GM-RAW \"is:important\"".to_string(),
+        };
+        self.session
+            .uid_search(query)
+            .map(|uids| uids.into_iter().collect())
+            .unwrap_or_default()
+    }
+
+    fn ensure_selected(&mut self, folder: &str) -> Result<()> {
+        if self.selected.as_deref() != Some(folder) {
+            self.session.select(folder)?;
+            self.selected = Some(folder.to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether the server has Gmail's extensions, labels among them
+    pub fn is_gmail(&self) -> bool {
+        self.gmail
+    }
+
+    /// Every selectable folder with its use and unread count, INBOX first
+    pub fn list_folders(&mut self) -> Result<Vec<FolderInfo>> {
+        let listing = self.session.list(Some(""), Some("*"))?;
+        let special = resolve_special(&listing, &self.overrides);
+        let mut folders: Vec<FolderInfo> = listing
+            .iter()
+            .filter(|f| !f.attributes().contains(&NameAttribute::NoSelect))
+            .map(|f| FolderInfo {
+                name: f.name().to_string(),
+                delimiter: f.delimiter().map(str::to_string),
+                unread: None,
+                special: SpecialUse::ALL
+                    .into_iter()
+                    .find(|u| special.get(u).is_some_and(|name| name == f.name())),
+            })
+            .collect();
+        self.special = Some(special);
+        folders.sort_by_key(|f| !f.name.eq_ignore_ascii_case("INBOX"));
+
+        for folder in &mut folders {
+            // The `imap` crate hands STATUS data to its unsolicited
+            // channel rather than the caller
+            let response = self.raw_command(&format!("STATUS {} (UNSEEN)", quote(&folder.name)))?;
+            folder.unread = parse::parse_status_unseen(&response);
+        }
+        Ok(folders)
     }
 
     pub fn select_folder(&mut self, folder: &str) -> Result<u32> {
         let mailbox = self.session.select(folder)?;
+        self.selected = Some(folder.to_string());
         Ok(mailbox.exists)
     }
 
-    pub fn fetch_emails(&mut self, folder: &str, start: u32, count: u32) -> Result<Vec<Email>> {
-        let total = self.session.select(folder)?.exists;
+    /// SELECT through a raw command, as the `imap` crate drops HIGHESTMODSEQ
+    fn select_with_modseq(&mut self, folder: &str) -> Result<SelectResponse> {
+        let response = self
+            .session
+            .run_command_and_read_response(format!("SELECT {}", quote(folder)))?;
+        self.selected = Some(folder.to_string());
+        Ok(parse::parse_select(&response))
+    }
+
+    /// The newest `count` messages of `folder`, skipping the newest `start`.
+    /// Bodies already in `cache` are reused, so only UIDs it lacks are
+    /// downloaded.
+    ///
+    /// When the cache has a HIGHESTMODSEQ from an earlier sync of the first
+    /// page, only what changed since is asked for: flags via CONDSTORE
+    /// CHANGEDSINCE, and expunged and new UIDs via one UID SEARCH. QRESYNC's
+    /// VANISHED would save the search, but the `imap` crate cannot parse it.
+    pub fn fetch_emails(
+        &mut self,
+        folder: &str,
+        start: u32,
+        count: u32,
+        cache: &mut FolderCache,
+    ) -> Result<Vec<Email>> {
+        let mailbox = self.select_with_modseq(folder)?;
+        cache.validate(mailbox.uid_validity);
+
+        let total = mailbox.exists;
         if total == 0 {
+            cache.retain(|_| false);
+            cache.highest_modseq = mailbox.highest_modseq.unwrap_or(0);
             return Ok(vec![]);
         }
 
+        let important_uids = self.important_uids(None);
+
+        let synced = match mailbox.highest_modseq {
+            Some(modseq) if start == 0 && cache.highest_modseq > 0 && !cache.is_empty() => {
+                self.sync_changes(folder, count, modseq, cache)?
+            }
+            _ => false,
+        };
+
+        let uids = if synced {
+            cache.uids().into_iter().rev().take(count as usize).collect()
+        } else {
+            self.list_page(folder, total, start, count, cache)?
+        };
+        if start == 0 {
+            cache.highest_modseq = mailbox.highest_modseq.unwrap_or(0);
+        }
+
+        let emails = uids
+            .into_iter()
+            .filter_map(|uid| {
+                let email = cache.get_mut(uid)?;
+                email.important = important_uids.contains(&uid);
+                Some(email.clone())
+            })
+            .collect();
+
+        Ok(emails)
+    }
+
+    /// Bring a cache that was current as of `cache.highest_modseq` up to
+    /// `modseq`. Returns false when too much arrived since and the first page
+    /// should be listed from scratch instead.
+    fn sync_changes(
+        &mut self,
+        folder: &str,
+        count: u32,
+        modseq: u64,
+        cache: &mut FolderCache,
+    ) -> Result<bool> {
+        let cached = cache.uids();
+        let (Some(&oldest), Some(&newest)) = (cached.first(), cached.last()) else {
+            return Ok(false);
+        };
+
+        // Everything still there from the oldest cached UID on
+        let present: std::collections::HashSet<u32> = self
+            .session
+            .uid_search(format!("UID {}:*", oldest))?
+            .into_iter()
+            .filter(|uid| *uid >= oldest)
+            .collect();
+        let mut arrived: Vec<u32> = present.iter().copied().filter(|uid| *uid > newest).collect();
+        if arrived.len() > count as usize {
+            return Ok(false);
+        }
+        cache.retain(|uid| present.contains(&uid));
+
+        if modseq > cache.highest_modseq {
+            let changes = self.session.uid_fetch(
+                format!("{}:*", oldest),
+                format!("(UID FLAGS) (CHANGEDSINCE {})", cache.highest_modseq),
+            )?;
+            let mut changed = Vec::new();
+            for msg in changes.iter() {
+                if let Some(email) = msg.uid.and_then(|uid| cache.get_mut(uid)) {
+                    email.seen = is_seen(msg);
+                    email.flagged = is_flagged(msg);
+                    changed.push(email.uid);
+                }
+            }
+            // A label change counts as a change too
+            self.refresh_labels(&changed, cache)?;
+        }
+
+        arrived.sort_unstable();
+        self.fetch_into_cache(folder, &arrived, cache)?;
+        Ok(true)
+    }
+
+    /// List one page by sequence number, refreshing flags of cached
+    /// messages and downloading the rest. Returns the UIDs, newest first.
+    fn list_page(
+        &mut self,
+        folder: &str,
+        total: u32,
+        start: u32,
+        count: u32,
+        cache: &mut FolderCache,
+    ) -> Result<Vec<u32>> {
         let end = total;
         let start_seq = if total > start + count {
             total - start - count + 1
@@ -85,110 +724,482 @@ impl ImapClient {
         };
         let range = format!("{}:{}", start_seq, end.saturating_sub(start).max(1));
 
-        // Get UIDs of important messages using Gmail's search extension
-        let important_uids: std::collections::HashSet<u32> = self
-            .session
-            .uid_search("X-GM-RAW \"is:important\"")
-            .map(|uids| uids.into_iter().collect())
-            .unwrap_or_default();
+        let listing = self.session.fetch(&range, "(UID FLAGS)")?;
+        let flags: Vec<(u32, bool, bool)> = listing
+            .iter()
+            .filter_map(|msg| Some((msg.uid?, is_seen(msg), is_flagged(msg))))
+            .collect();
+        let (Some(&(oldest, ..)), Some(&(newest, ..))) = (flags.first(), flags.last()) else {
+            return Ok(vec![]);
+        };
+
+        // Gone from the server, or older than a first page whose flags
+        // are not tracked anymore
+        let listed: std::collections::HashSet<u32> = flags.iter().map(|(uid, ..)| *uid).collect();
+        cache.retain(|uid| {
+            listed.contains(&uid) || uid > newest || (uid < oldest && start > 0)
+        });
+
+        let missing: Vec<u32> = flags
+            .iter()
+            .map(|(uid, ..)| *uid)
+            .filter(|uid| !cache.contains(*uid))
+            .collect();
+        self.fetch_into_cache(folder, &missing, cache)?;
+
+        for &(uid, seen, flagged) in &flags {
+            if let Some(email) = cache.get_mut(uid) {
+                email.seen = seen;
+                email.flagged = flagged;
+            }
+        }
+        let cached: Vec<u32> = flags
+            .iter()
+            .map(|(uid, ..)| *uid)
+            .filter(|uid| !missing.contains(uid))
+            .collect();
+        self.refresh_labels(&cached, cache)?;
+
+        Ok(flags.iter().rev().map(|(uid, ..)| *uid).collect())
+    }
+
+    /// Bring Gmail labels of cached messages up to date, as their flags are
+    fn refresh_labels(&mut self, uids: &[u32], cache: &mut FolderCache) -> Result<()> {
+        for (uid, attrs) in self.gmail_attrs(&uid_set(uids))? {
+            if let Some(email) = cache.get_mut(uid) {
+                email.labels = attrs.labels;
+            }
+        }
+        Ok(())
+    }
 
-        let messages = self.session.fetch(&range, "(UID FLAGS BODY.PEEK[])")?;
+    fn fetch_into_cache(&mut self, folder: &str, uids: &[u32], cache: &mut FolderCache) -> Result<()> {
+        if uids.is_empty() {
+            return Ok(());
+        }
+        let uid_list = uid_set(uids);
+        let messages = self.session.uid_fetch(&uid_list, LIST_QUERY)?;
+        let gmail = self.gmail_attrs(&uid_list)?;
         let parser = MessageParser::default();
+        for msg in messages.iter() {
+            if let Some(mut email) = parse_message(&parser, msg, folder, false) {
+                add_gmail_attrs(&mut email, &gmail);
+                cache.insert(email);
+            }
+        }
+        Ok(())
+    }
+
+    /// Highest UID currently in `folder`, 0 when it is empty
+    pub fn last_uid(&mut self, folder: &str) -> Result<u32> {
+        self.select_folder(folder)?;
+        let uids = self.session.uid_search("UID *")?;
+        Ok(uids.into_iter().max().unwrap_or(0))
+    }
 
+    /// Messages in `folder` that arrived after `after_uid`, newest first
+    pub fn fetch_new(&mut self, folder: &str, after_uid: u32) -> Result<Vec<Email>> {
+        self.ensure_selected(folder)?;
+        // `n:*` always matches the highest UID, even when it is below n
+        let mut uids: Vec<u32> = self
+            .session
+            .uid_search(format!("UID {}:*", after_uid + 1))?
+            .into_iter()
+            .filter(|uid| *uid > after_uid)
+            .collect();
+        if uids.is_empty() {
+            return Ok(vec![]);
+        }
+        uids.sort_unstable();
+        let uid_list = uid_set(&uids);
+
+        let important_uids = self.important_uids(Some(&uid_list));
+
+        let messages = self.session.uid_fetch(&uid_list, LIST_QUERY)?;
+        let gmail = self.gmail_attrs(&uid_list)?;
+        let parser = MessageParser::default();
         let mut emails: Vec<Email> = messages
             .iter()
             .filter_map(|msg| {
-                let uid = msg.uid?;
-                let body = msg.body()?;
-                let parsed = parser.parse(body)?;
-
-                let subject = parsed
-                    .subject()
-                    .unwrap_or("(No Subject)")
-                    .to_string();
-
-                let from_addr = parsed
-                    .from()
-                    .and_then(|f| f.first());
-                
-                let from = from_addr
-                    .map(|a| {
-                        a.name()
-                            .map(|n| n.to_string())
-                            .unwrap_or_else(|| a.address().unwrap_or("").to_string())
-                    })
-                    .unwrap_or_else(|| "(Unknown)".to_string());
-                
-                let from_address = from_addr
-                    .and_then(|a| a.address())
-                    .unwrap_or("")
-                    .to_string();
-
-                let date = parsed.date().map(|d| {
-                    DateTime::from_timestamp(d.to_timestamp(), 0).unwrap_or_default()
-                });
-
-                let body_text = if let Some(html) = parsed.body_html(0) {
-                    html_to_text(&html)
-                } else if let Some(text) = parsed.body_text(0) {
-                    text.to_string()
-                } else {
-                    String::new()
-                };
-
-                let seen = msg.flags().iter().any(|f| matches!(f, imap::types::Flag::Seen));
-                let important = important_uids.contains(&uid);
-
-                let message_id = parsed.message_id().map(|s| s.to_string());
-                let in_reply_to = parsed.in_reply_to()
-                    .as_text_list()
-                    .and_then(|v| v.first().map(|s| s.to_string()));
-                let references: Vec<String> = parsed
-                    .references()
-                    .as_text_list()
-                    .map(|v| v.iter().map(|s| s.to_string()).collect())
-                    .unwrap_or_default();
+                let important = msg.uid.is_some_and(|uid| important_uids.contains(&uid));
+                let mut email = parse_message(&parser, msg, folder, important)?;
+                add_gmail_attrs(&mut email, &gmail);
+                Some(email)
+            })
+            .collect();
+
+        emails.sort_by_key(|e| std::cmp::Reverse(e.uid));
+        Ok(emails)
+    }
+
+    /// A page of the messages matching `query`, newest first, skipping the
+    /// newest `start`. On Gmail the query is in Gmail's own syntax
+    /// (X-GM-RAW) and covers All Mail; elsewhere it is looked for in the
+    /// text of INBOX messages.
+    pub fn search(&mut self, query: &str, start: u32, count: u32) -> Result<Vec<Email>> {
+        let (folder, criteria) = if self.gmail {
+            (self.special_folder(SpecialUse::All)?, format!("X-GM-RAW {}", quote(query)))
+        } else {
+            ("INBOX".to_string(), format!("TEXT {}", quote(query)))
+        };
+        self.search_page(&folder, &criteria, start, count)
+    }
 
-                Some(Email {
-                    uid,
-                    subject,
-                    from,
-                    from_address,
-                    date,
-                    body: body_text,
-                    seen,
-                    important,
-                    message_id,
-                    in_reply_to,
-                    references,
-                })
+    /// A page of the flagged messages in INBOX, for servers that have no
+    /// folder gathering them the way Gmail's Starred does
+    pub fn flagged(&mut self, start: u32, count: u32) -> Result<Vec<Email>> {
+        self.search_page("INBOX", "FLAGGED", start, count)
+    }
+
+    /// A page of the messages of `folder` matching `criteria`, newest first
+    fn search_page(&mut self, folder: &str, criteria: &str, start: u32, count: u32) -> Result<Vec<Email>> {
+        self.ensure_selected(folder)?;
+        let mut uids: Vec<u32> = self.session.uid_search(criteria)?.into_iter().collect();
+        uids.sort_unstable_by(|a, b| b.cmp(a));
+        let page: Vec<u32> = uids.into_iter().skip(start as usize).take(count as usize).collect();
+        if page.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let uid_list = uid_set(&page);
+        let messages = self.session.uid_fetch(&uid_list, LIST_QUERY)?;
+        let gmail = self.gmail_attrs(&uid_list)?;
+        let parser = MessageParser::default();
+        let mut emails: Vec<Email> = messages
+            .iter()
+            .filter_map(|msg| {
+                let mut email = parse_message(&parser, msg, folder, false)?;
+                add_gmail_attrs(&mut email, &gmail);
+                Some(email)
             })
             .collect();
 
-        emails.reverse();
+        emails.sort_by_key(|e| std::cmp::Reverse(e.uid));
         Ok(emails)
     }
 
-    pub fn mark_as_read(&mut self, uid: u32) -> Result<()> {
+    /// Download the text of one message, only the part `section` names
+    /// when it has one, so attachments stay on the server
+    pub fn fetch_body(&mut self, folder: &str, uid: u32, section: Option<&str>) -> Result<String> {
+        self.ensure_selected(folder)?;
+        let query = match section {
+            Some(section) => format!("(BODY.PEEK[{0}.MIME] BODY.PEEK[{0}])", section),
+            None => "BODY.PEEK[]".to_string(),
+        };
+        let messages = self.session.uid_fetch(uid.to_string(), query)?;
+        let msg = messages
+            .iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Message {} is gone from {}", uid, folder))?;
+
+        // A part's MIME header plus its content parses as a message of its own
+        let raw = match section {
+            Some(section) => {
+                let path: Vec<u32> = section.split('.').filter_map(|p| p.parse().ok()).collect();
+                let mime = msg
+                    .section(&SectionPath::Part(path.clone(), Some(MessageSection::Mime)))
+                    .unwrap_or_default();
+                let content = msg.section(&SectionPath::Part(path, None)).unwrap_or_default();
+                let separator: &[u8] = if mime.ends_with(b"\r\n\r\n") { b"" } else { b"\r\n" };
+                [mime, separator, content].concat()
+            }
+            None => msg.body().unwrap_or_default().to_vec(),
+        };
+        let parsed = MessageParser::default()
+            .parse(&raw)
+            .ok_or_else(|| anyhow::anyhow!("Could not parse message {}", uid))?;
+        Ok(message_text(&parsed))
+    }
+
+    pub fn supports_idle(&mut self) -> bool {
         self.session
-            .uid_store(uid.to_string(), "+FLAGS (\\Seen)")?;
+            .capabilities()
+            .map(|caps| caps.has_str("IDLE"))
+            .unwrap_or(false)
+    }
+
+    /// Block in IDLE on the selected folder until the server reports a
+    /// change or `timeout` passes
+    pub fn idle(&mut self, timeout: Duration) -> Result<()> {
+        self.session.idle()?.wait_with_timeout(timeout)?;
         Ok(())
     }
 
-    pub fn delete_email(&mut self, uid: u32) -> Result<()> {
+    pub fn noop(&mut self) -> Result<()> {
+        self.session.noop()?;
+        Ok(())
+    }
+
+    pub fn mark_as_read(&mut self, folder: &str, uid: u32) -> Result<()> {
+        self.ensure_selected(folder)?;
         self.session
-            .uid_store(uid.to_string(), "+FLAGS (\\Deleted)")?;
-        self.session.expunge()?;
+            .uid_store(uid.to_string(), "+FLAGS (\\Seen)")?;
         Ok(())
     }
 
-    pub fn archive_email(&mut self, uid: u32) -> Result<()> {
-        // Move to All Mail (Gmail's archive)
-        self.session.uid_mv(uid.to_string(), "[Gmail]/All Mail")?;
+    /// Star or unstar a message
+    pub fn set_flagged(&mut self, folder: &str, uid: u32, flagged: bool) -> Result<()> {
+        self.ensure_selected(folder)?;
+        let change = if flagged { "+FLAGS.SILENT (\\Flagged)" } else { "-FLAGS.SILENT (\\Flagged)" };
+        self.session.uid_store(uid.to_string(), change)?;
+        Ok(())
+    }
+
+    /// Put a Gmail label on messages, or take it off
+    pub fn set_label(&mut self, folder: &str, uids: &[u32], label: &str, add: bool) -> Result<()> {
+        if !self.gmail {
+            anyhow::bail!("Labels need a Gmail account");
+        }
+        self.ensure_selected(folder)?;
+        let change = if add { "+X-GM-LABELS.SILENT" } else { "-X-GM-LABELS.SILENT" };
+        // Gmail echoes X-GM-LABELS back, which the `imap` crate cannot read
+        self.raw_command(&format!("UID STORE {} {} ({})", uid_set(uids), change, quote(label)))?;
         Ok(())
     }
 
+    /// Move messages to Trash, or delete them for good if they are there
+    /// already. Nothing else in the folder is expunged along with them.
+    pub fn delete_emails(&mut self, folder: &str, uids: &[u32]) -> Result<()> {
+        let trash = self.special_folder(SpecialUse::Trash)?;
+        self.ensure_selected(folder)?;
+        let uid_set = uid_set(uids);
+        if folder == trash {
+            self.session.uid_store(&uid_set, "+FLAGS.SILENT (\\Deleted)")?;
+            self.expunge_uids(uids)?;
+        } else {
+            self.move_uids(uids, &trash)?;
+        }
+        Ok(())
+    }
+
+    /// Move messages of the selected folder to `to`. Without MOVE they are
+    /// copied, then flagged \Deleted and expunged.
+    fn move_uids(&mut self, uids: &[u32], to: &str) -> Result<()> {
+        let uid_set = uid_set(uids);
+        if self.can_move {
+            self.session.uid_mv(&uid_set, to)?;
+            return Ok(());
+        }
+        self.session.uid_copy(&uid_set, to)?;
+        self.session.uid_store(&uid_set, "+FLAGS.SILENT (\\Deleted)")?;
+        self.expunge_uids(uids)
+    }
+
+    /// File messages in another folder. Out of Gmail's All Mail they are
+    /// copied instead, as taking them out of there deletes them; on
+    /// Gmail that puts the other folder's label on.
+    pub fn move_emails(&mut self, folder: &str, uids: &[u32], to: &str) -> Result<()> {
+        let all_mail = self.special_folder(SpecialUse::All).ok();
+        self.ensure_selected(folder)?;
+        if all_mail.as_deref() == Some(folder) {
+            self.session.uid_copy(uid_set(uids), to)?;
+            return Ok(());
+        }
+        self.move_uids(uids, to)
+    }
+
+    /// The folder for `use_`: the configured one, else the one the server
+    /// marks, else one by a common name
+    pub fn special_folder(&mut self, use_: SpecialUse) -> Result<String> {
+        if self.special.is_none() {
+            let listing = self.session.list(Some(""), Some("*"))?;
+            self.special = Some(resolve_special(&listing, &self.overrides));
+        }
+        self.special
+            .as_ref()
+            .and_then(|special| special.get(&use_))
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No {:?} folder found, set one under [accounts.folders]", use_))
+    }
+
+    /// Expunge `uids` of the selected folder, which are flagged \Deleted
+    /// already, leaving any other \Deleted message alone
+    fn expunge_uids(&mut self, uids: &[u32]) -> Result<()> {
+        if self.uidplus {
+            self.session.uid_expunge(uid_set(uids))?;
+            return Ok(());
+        }
+
+        // A plain EXPUNGE takes every \Deleted message, so the others are
+        // unflagged for the duration
+        let spared: Vec<u32> = self
+            .session
+            .uid_search("DELETED")?
+            .into_iter()
+            .filter(|uid| !uids.contains(uid))
+            .collect();
+        if spared.is_empty() {
+            self.session.expunge()?;
+            return Ok(());
+        }
+        let spared = uid_set(&spared);
+        self.session.uid_store(&spared, "-FLAGS.SILENT (\\Deleted)")?;
+        let expunged = self.session.expunge();
+        self.session.uid_store(&spared, "+FLAGS.SILENT (\\Deleted)")?;
+        expunged?;
+        Ok(())
+    }
+
+    pub fn archive_emails(&mut self, folder: &str, uids: &[u32]) -> Result<()> {
+        let archive = self.special_folder(SpecialUse::Archive)?;
+        self.ensure_selected(folder)?;
+        if folder == archive {
+            // Gmail's archive is All Mail, where search results are from;
+            // there archiving is taking the inbox label off
+            if self.gmail {
+                self.raw_command(&format!("UID STORE {} -X-GM-LABELS.SILENT (\\Inbox)", uid_set(uids)))?;
+            }
+            return Ok(());
+        }
+        self.move_uids(uids, &archive)
+    }
+
+    /// Bring a snoozed message back to the inbox, unread, from whatever
+    /// folder it was moved to since. It is looked for by Gmail's message ID
+    /// where there is one, as that survives every move, otherwise by
+    /// Message-ID. The inbox is tried first, in case it was moved back by
+    /// hand, then Gmail's All Mail, then every other folder.
+    pub fn resurface(&mut self, message_id: Option<&str>, gmail_id: Option<u64>) -> Result<ReminderOutcome> {
+        let Some(query) = self.message_query(message_id, gmail_id) else {
+            return Ok(ReminderOutcome::Missing);
+        };
+        let (all_mail, others) = self.searchable_folders()?;
+        self.bring_to_inbox(&query, &all_mail, &others, false)
+    }
+
+    /// Put messages that were archived or deleted out of `folder` back
+    /// into it, looking for each the way `resurface` does. Flags travel
+    /// with the message. Returns how many are back.
+    pub fn restore(&mut self, folder: &str, messages: &[MessageRef]) -> Result<usize> {
+        let (all_mail, others) = self.searchable_folders()?;
+        let folders: Vec<String> = std::iter::once(folder.to_string())
+            .chain(all_mail.iter().chain(others.iter()).filter(|f| *f != folder).cloned())
+            .collect();
+
+        let mut restored = 0;
+        for (message_id, gmail_id) in messages {
+            let Some(query) = self.message_query(message_id.as_deref(), *gmail_id) else {
+                continue;
+            };
+            let Some((found, uid)) = self.locate(&query, &folders)? else {
+                continue;
+            };
+            if found != folder {
+                if all_mail.contains(&found) {
+                    self.session.uid_copy(uid.to_string(), folder)?;
+                } else {
+                    self.move_uids(&[uid], folder)?;
+                }
+            }
+            restored += 1;
+        }
+        Ok(restored)
+    }
+
+    /// A SEARCH key for one message, by Gmail's ID where the server has them
+    fn message_query(&self, message_id: Option<&str>, gmail_id: Option<u64>) -> Option<String> {
+        match (gmail_id.filter(|_| self.gmail), message_id) {
+            (Some(id), _) => Some(format!("X-GM-MSGID {}", id)),
+            (None, Some(id)) => Some(format!("HEADER Message-ID {}", quote(id))),
+            (None, None) => None,
+        }
+    }
+
+    /// Resurface a message sent from `me` unless someone has replied to it
+    pub fn follow_up(&mut self, message_id: &str, me: &str) -> Result<ReminderOutcome> {
+        let (all_mail, others) = self.searchable_folders()?;
+
+        let replies = format!(
+            "NOT FROM {} OR HEADER In-Reply-To {} HEADER References {}",
+            quote(me),
+            quote(message_id),
+            quote(message_id)
+        );
+        // All Mail holds everything but spam and trash, so one search does
+        let folders = if all_mail.is_empty() {
+            std::iter::once("INBOX".to_string()).chain(others.iter().cloned()).collect()
+        } else {
+            all_mail.clone()
+        };
+        for folder in folders {
+            self.ensure_selected(&folder)?;
+            if !self.session.uid_search(&replies)?.is_empty() {
+                return Ok(ReminderOutcome::Replied);
+            }
+        }
+
+        let query = format!("HEADER Message-ID {}", quote(message_id));
+        self.bring_to_inbox(&query, &all_mail, &others, true)
+    }
+
+    /// Selectable folders other than INBOX, split into Gmail's All Mail
+    /// and the rest
+    fn searchable_folders(&mut self) -> Result<(Vec<String>, Vec<String>)> {
+        let listing = self.session.list(Some(""), Some("*"))?;
+        let mut all_mail = Vec::new();
+        let mut others = Vec::new();
+        for folder in listing.iter() {
+            let attributes = folder.attributes();
+            if attributes.contains(&NameAttribute::NoSelect) || folder.name().eq_ignore_ascii_case("INBOX") {
+                continue;
+            }
+            let all = attributes
+                .iter()
+                .any(|a| matches!(a, NameAttribute::Custom(c) if c.eq_ignore_ascii_case("\\All")));
+            if all { &mut all_mail } else { &mut others }.push(folder.name().to_string());
+        }
+        Ok((all_mail, others))
+    }
+
+    /// Find the newest message matching `query`, looking in INBOX first,
+    /// and put it in the inbox unread. With `keep` a message found outside
+    /// the inbox is copied rather than moved, and the original stays read.
+    fn bring_to_inbox(
+        &mut self,
+        query: &str,
+        all_mail: &[String],
+        others: &[String],
+        keep: bool,
+    ) -> Result<ReminderOutcome> {
+        let folders: Vec<String> = std::iter::once("INBOX".to_string())
+            .chain(all_mail.iter().cloned())
+            .chain(others.iter().cloned())
+            .collect();
+
+        let Some((folder, uid)) = self.locate(query, &folders)? else {
+            return Ok(ReminderOutcome::Missing);
+        };
+        self.session.uid_store(uid.to_string(), "-FLAGS.SILENT (\\Seen)")?;
+        if folder == "INBOX" {
+            return Ok(ReminderOutcome::Resurfaced);
+        }
+        if all_mail.contains(&folder) {
+            // Taking a message out of All Mail deletes it, a copy just
+            // gives it the Inbox label back
+            self.session.uid_copy(uid.to_string(), "INBOX")?;
+        } else if keep {
+            self.session.uid_copy(uid.to_string(), "INBOX")?;
+            self.session.uid_store(uid.to_string(), "+FLAGS.SILENT (\\Seen)")?;
+        } else {
+            self.move_uids(&[uid], "INBOX")?;
+        }
+        Ok(ReminderOutcome::Resurfaced)
+    }
+
+    /// The first of `folders` holding a message that matches `query`, and
+    /// the newest such message's UID. That folder is left selected.
+    fn locate(&mut self, query: &str, folders: &[String]) -> Result<Option<(String, u32)>> {
+        for folder in folders {
+            self.ensure_selected(folder)?;
+            if let Some(uid) = self.session.uid_search(query)?.into_iter().max() {
+                return Ok(Some((folder.clone(), uid)));
+            }
+        }
+        Ok(None)
+    }
+
     pub fn fetch_thread(&mut self, email: &Email) -> Result<Vec<Email>> {
+        self.ensure_selected(&email.folder)?;
         let mut message_ids: Vec<String> = email.references.clone();
         if let Some(ref in_reply_to) = email.in_reply_to {
             if !message_ids.contains(in_reply_to) {
@@ -226,12 +1237,7 @@ impl ImapClient {
 
         let uid_list: String = uids.iter().map(|u| u.to_string()).collect::<Vec<_>>().join(",");
         
-        // Get important UIDs for this thread
-        let important_uids: std::collections::HashSet<u32> = self
-            .session
-            .uid_search("X-GM-RAW \"is:important\"")
-            .map(|uids| uids.into_iter().collect())
-            .unwrap_or_default();
+        let important_uids = self.important_uids(None);
         
         let messages = self.session.uid_fetch(&uid_list, "(UID FLAGS BODY.PEEK[])")?;
         let parser = MessageParser::default();
@@ -239,57 +1245,30 @@ impl ImapClient {
         let mut emails: Vec<Email> = messages
             .iter()
             .filter_map(|msg| {
-                let uid = msg.uid?;
-                let body = msg.body()?;
-                let parsed = parser.parse(body)?;
-
-                let subject = parsed.subject().unwrap_or("(No Subject)").to_string();
-                let from_addr = parsed.from().and_then(|f| f.first());
-                let from = from_addr
-                    .map(|a| a.name().map(|n| n.to_string()).unwrap_or_else(|| a.address().unwrap_or("").to_string()))
-                    .unwrap_or_else(|| "(Unknown)".to_string());
-                let from_address = from_addr.and_then(|a| a.address()).unwrap_or("").to_string();
-                let date = parsed.date().map(|d| DateTime::from_timestamp(d.to_timestamp(), 0).unwrap_or_default());
-                let body_text = if let Some(html) = parsed.body_html(0) {
-                    html_to_text(&html)
-                } else if let Some(text) = parsed.body_text(0) {
-                    text.to_string()
-                } else {
-                    String::new()
-                };
-                let seen = msg.flags().iter().any(|f| matches!(f, imap::types::Flag::Seen));
-                let important = important_uids.contains(&uid);
-                let message_id = parsed.message_id().map(|s| s.to_string());
-                let in_reply_to = parsed.in_reply_to()
-                    .as_text_list()
-                    .and_then(|v| v.first().map(|s| s.to_string()));
-                let references: Vec<String> = parsed
-                    .references()
-                    .as_text_list()
-                    .map(|v| v.iter().map(|s| s.to_string()).collect())
-                    .unwrap_or_default();
-
-                Some(Email { uid, subject, from, from_address, date, body: body_text, seen, important, message_id, in_reply_to, references })
+                let important = msg.uid.is_some_and(|uid| important_uids.contains(&uid));
+                let mut parsed = parse_message(&parser, msg, &email.folder, important)?;
+                parsed.account = email.account;
+                Some(parsed)
             })
             .collect();
 
-        emails.sort_by(|a, b| a.date.cmp(&b.date));
+        emails.sort_by_key(|e| e.date);
         Ok(emails)
     }
 
-    pub fn save_draft(&mut self, from: &str, to: &str, cc: &str, subject: &str, body: &str) -> Result<()> {
+    pub fn save_draft(&mut self, from: &str, draft: &OutgoingEmail) -> Result<()> {
         // Convert message to string format for IMAP
         let email_bytes = format!(
             "From: {}\r\nTo: {}\r\n{}\r\nSubject: {}\r\nContent-Type: text/plain; charset=\"UTF-8\"\r\n\r\n{}",
             from,
-            to,
-            if cc.is_empty() { String::new() } else { format!("Cc: {}\r\n", cc) },
-            subject,
-            body
+            draft.to,
+            if draft.cc.is_empty() { String::new() } else { format!("Cc: {}\r\n", draft.cc) },
+            draft.subject,
+            draft.body
         );
         
-        // Append to Drafts folder
-        self.session.append("[Gmail]/Drafts", email_bytes.as_bytes())?;
+        let drafts = self.special_folder(SpecialUse::Drafts)?;
+        self.session.append(&drafts, email_bytes.as_bytes())?;
         Ok(())
     }
 
@@ -299,16 +1278,8 @@ impl ImapClient {
     }
 }
 
-pub fn send_email(
-    from: &str,
-    to: &str,
-    cc: &str,
-    subject: &str,
-    body: &str,
-    access_token: &str,
-    in_reply_to: Option<&str>,
-    references: &[String],
-) -> Result<()> {
+/// Send `email`, returning the Message-ID it went out with
+pub fn send_email(config: &AccountConfig, email: &OutgoingEmail) -> Result<String> {
     use lettre::{
         message::header::ContentType,
         transport::smtp::{
@@ -319,41 +1290,64 @@ pub fn send_email(
     };
 
     let mut builder = Message::builder()
-        .from(from.parse()?)
-        .to(to.parse()?)
-        .subject(subject);
+        .from(config.email.parse()?)
+        .to(email.to.parse()?)
+        .subject(&email.subject);
     
     // Add CC if provided
-    if !cc.is_empty() {
-        builder = builder.cc(cc.parse()?);
+    if !email.cc.is_empty() {
+        builder = builder.cc(email.cc.parse()?);
     }
 
-    if let Some(reply_to) = in_reply_to {
-        let msg_id: String = reply_to.parse().unwrap();
-        builder = builder.in_reply_to(msg_id);
+    if let Some(ref reply_to) = email.in_reply_to {
+        builder = builder.in_reply_to(reply_to.clone());
     }
 
-    if !references.is_empty() {
-        let refs_str: String = references.join(" ").parse().unwrap();
-        builder = builder.references(refs_str);
+    if !email.references.is_empty() {
+        builder = builder.references(email.references.join(" "));
     }
 
-    let email = builder
+    // Chosen here rather than left to lettre, so replies can be matched to it
+    let message_id = new_message_id(&config.email);
+    builder = builder.message_id(Some(format!("<{}>", message_id)));
+
+    let message = builder
         .header(ContentType::TEXT_PLAIN)
-        .body(body.to_string())?;
+        .body(email.body.clone())?;
 
-    let creds = Credentials::new(from.to_string(), access_token.to_string());
+    let creds = Credentials::new(config.username().to_string(), config.secret()?.to_string());
+    let mechanism = match config.auth {
+        AuthMethod::Xoauth2 => Mechanism::Xoauth2,
+        AuthMethod::Plain => Mechanism::Plain,
+        AuthMethod::Login => Mechanism::Login,
+    };
 
-    let tls_params = TlsParameters::builder("smtp.gmail.com".to_string())
+    let server = &config.smtp;
+    let tls_params = TlsParameters::builder(server.host.clone())
+        .dangerous_accept_invalid_certs(config.accept_invalid_certs)
         .build_native()?;
+    let tls = match server.tls {
+        TlsMode::Implicit => Tls::Wrapper(tls_params),
+        TlsMode::Starttls => Tls::Required(tls_params),
+    };
 
-    let mailer = SmtpTransport::builder_dangerous("smtp.gmail.com")
-        .port(465)
-        .tls(Tls::Wrapper(tls_params))
+    let mailer = SmtpTransport::builder_dangerous(&server.host)
+        .port(server.port)
+        .tls(tls)
         .credentials(creds)
-        .authentication(vec![Mechanism::Xoauth2])
+        .authentication(vec![mechanism])
         .build();
 
-    mailer.send(&email)?;
-    Ok(())
+    mailer.send(&message)?;
+    Ok(message_id)
+}
+
+/// A Message-ID unique to this moment and process, on the sender's domain
+fn new_message_id(from: &str) -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let domain = from.rsplit_once('@').map(|(_, d)| d).unwrap_or("localhost");
+    format!("{}.{}@{}", nanos, std::process::id(), domain)
 }
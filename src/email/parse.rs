@@ -0,0 +1,297 @@
+//! Readers for the parts of raw IMAP responses the `imap` crate drops.
+
+/// What a SELECT reports about the mailbox
+#[derive(Debug, Default)]
+pub struct SelectResponse {
+    pub exists: u32,
+    pub uid_validity: u32,
+    /// Only reported by servers with CONDSTORE
+    pub highest_modseq: Option<u64>,
+}
+
+/// Read the untagged lines of a SELECT response, e.g.
+/// `* 172 EXISTS` and `* OK [HIGHESTMODSEQ 715194045007]`
+pub fn parse_select(response: &[u8]) -> SelectResponse {
+    let text = String::from_utf8_lossy(response);
+    let mut select = SelectResponse::default();
+
+    for line in text.lines() {
+        let Some(rest) = line.strip_prefix("* ") else {
+            continue;
+        };
+        if let Some(count) = rest.strip_suffix(" EXISTS") {
+            select.exists = count.trim().parse().unwrap_or(0);
+        } else if let Some(value) = response_code(rest, "UIDVALIDITY") {
+            select.uid_validity = value.parse().unwrap_or(0);
+        } else if let Some(value) = response_code(rest, "HIGHESTMODSEQ") {
+            select.highest_modseq = value.parse().ok();
+        }
+    }
+
+    select
+}
+
+/// The argument of `OK [NAME value]`
+fn response_code<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let code = line.strip_prefix("OK [")?;
+    let value = code.strip_prefix(name)?.strip_prefix(' ')?;
+    value.split(']').next().map(str::trim)
+}
+
+/// Quote a mailbox name as an IMAP string
+pub fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// One value of a raw response
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Atom(String),
+    Str(String),
+    List(Vec<Value>),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Atom(s) | Value::Str(s) => Some(s),
+            Value::List(_) => None,
+        }
+    }
+}
+
+/// Gmail's extension attributes of one message
+#[derive(Debug, Default)]
+pub struct GmailAttrs {
+    pub uid: u32,
+    pub thread_id: Option<u64>,
+    pub message_id: Option<u64>,
+    /// X-GM-LABELS, system ones like `\Inbox` included
+    pub labels: Vec<String>,
+}
+
+/// Read the FETCH lines of a response to `UID FETCH ... (UID X-GM-...)`,
+/// e.g. `* 12 FETCH (X-GM-THRID 1278455344230334865 UID 1 X-GM-LABELS (\Inbox "Work"))`
+pub fn parse_gmail_fetch(response: &[u8]) -> Vec<GmailAttrs> {
+    let mut reader = Reader { data: response, pos: 0 };
+    let mut result = Vec::new();
+
+    while let Some(line) = reader.line() {
+        let [Value::Atom(star), _, Value::Atom(fetch), Value::List(items)] = line.as_slice() else {
+            continue;
+        };
+        if star != "*" || !fetch.eq_ignore_ascii_case("FETCH") {
+            continue;
+        }
+
+        let mut attrs = GmailAttrs::default();
+        for pair in items.chunks(2) {
+            let [Value::Atom(name), value] = pair else {
+                continue;
+            };
+            match name.to_ascii_uppercase().as_str() {
+                "UID" => attrs.uid = value.as_str().and_then(|v| v.parse().ok()).unwrap_or(0),
+                "X-GM-THRID" => attrs.thread_id = value.as_str().and_then(|v| v.parse().ok()),
+                "X-GM-MSGID" => attrs.message_id = value.as_str().and_then(|v| v.parse().ok()),
+                "X-GM-LABELS" => {
+                    if let Value::List(labels) = value {
+                        attrs.labels = labels.iter().filter_map(|l| l.as_str().map(str::to_string)).collect();
+                    }
+                }
+                _ => {}
+            }
+        }
+        if attrs.uid != 0 {
+            result.push(attrs);
+        }
+    }
+
+    result
+}
+
+/// The UNSEEN count of a response to `STATUS name (UNSEEN)`, e.g.
+/// `* STATUS "[Gmail]/Spam" (UNSEEN 3)`
+pub fn parse_status_unseen(response: &[u8]) -> Option<u32> {
+    let mut reader = Reader { data: response, pos: 0 };
+    while let Some(line) = reader.line() {
+        let [Value::Atom(star), Value::Atom(status), _, Value::List(items)] = line.as_slice() else {
+            continue;
+        };
+        if star != "*" || !status.eq_ignore_ascii_case("STATUS") {
+            continue;
+        }
+        for pair in items.chunks(2) {
+            if let [Value::Atom(name), value] = pair {
+                if name.eq_ignore_ascii_case("UNSEEN") {
+                    return value.as_str().and_then(|v| v.parse().ok());
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Splits raw response bytes into lines of values, reading literals
+/// (`{n}` followed by n bytes) as strings
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    /// The next line, None at the end of the response
+    fn line(&mut self) -> Option<Vec<Value>> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let mut values = Vec::new();
+        loop {
+            match self.peek() {
+                None => break,
+                Some(b'\n') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b' ' | b'\r') => self.pos += 1,
+                Some(_) => match self.value() {
+                    Some(value) => values.push(value),
+                    // Skip what cannot be read, up to the end of the line
+                    None => {
+                        while !matches!(self.peek(), None | Some(b'\n')) {
+                            self.pos += 1;
+                        }
+                    }
+                },
+            }
+        }
+        Some(values)
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        match self.peek()? {
+            b'(' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    match self.peek()? {
+                        b')' => {
+                            self.pos += 1;
+                            return Some(Value::List(items));
+                        }
+                        b' ' => self.pos += 1,
+                        b'\r' | b'\n' => return None,
+                        _ => items.push(self.value()?),
+                    }
+                }
+            }
+            b'"' => {
+                self.pos += 1;
+                let mut text = Vec::new();
+                loop {
+                    match self.peek()? {
+                        b'"' => {
+                            self.pos += 1;
+                            return Some(Value::Str(String::from_utf8_lossy(&text).into_owned()));
+                        }
+                        b'\\' => {
+                            text.push(*self.data.get(self.pos + 1)?);
+                            self.pos += 2;
+                        }
+                        c => {
+                            text.push(c);
+                            self.pos += 1;
+                        }
+                    }
+                }
+            }
+            b'{' => {
+                let close = self.pos + self.data[self.pos..].iter().position(|&c| c == b'}')?;
+                let len: usize = std::str::from_utf8(&self.data[self.pos + 1..close]).ok()?.parse().ok()?;
+                let start = close + 1 + self.data[close + 1..].iter().position(|&c| c == b'\n')? + 1;
+                let bytes = self.data.get(start..start + len)?;
+                self.pos = start + len;
+                Some(Value::Str(String::from_utf8_lossy(bytes).into_owned()))
+            }
+            _ => {
+                let start = self.pos;
+                let mut depth = 0;
+                while let Some(c) = self.peek() {
+                    match c {
+                        b'[' => depth += 1,
+                        b']' => depth -= 1,
+                        b' ' | b'(' | b')' | b'\r' | b'\n' if depth == 0 => break,
+                        _ => {}
+                    }
+                    self.pos += 1;
+                }
+                Some(Value::Atom(String::from_utf8_lossy(&self.data[start..self.pos]).into_owned()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_counts_and_codes() {
+        let response = b"* FLAGS (\\Answered \\Flagged \\Draft \\Deleted \\Seen $NotPhishing $Phishing)\r\n\
+* OK [PERMANENTFLAGS (\\Answered \\Flagged \\Draft \\Deleted \\Seen $NotPhishing $Phishing \\*)] Flags permitted.\r\n\
+* OK [UIDVALIDITY 1] UIDs valid.\r\n\
+* 172 EXISTS\r\n\
+* 0 RECENT\r\n\
+* OK [UIDNEXT 5913] Predicted next UID.\r\n\
+* OK [HIGHESTMODSEQ 715194045007]\r\n\
+a3 OK [READ-WRITE] INBOX selected. (Success)\r\n";
+        let select = parse_select(response);
+        assert_eq!(select.exists, 172);
+        assert_eq!(select.uid_validity, 1);
+        assert_eq!(select.highest_modseq, Some(715194045007));
+    }
+
+    #[test]
+    fn select_without_condstore() {
+        let response = b"* 3 EXISTS\r\n* OK [UIDVALIDITY 1699999999]\r\na1 OK [READ-WRITE] SELECT completed\r\n";
+        let select = parse_select(response);
+        assert_eq!(select.exists, 3);
+        assert_eq!(select.uid_validity, 1699999999);
+        assert_eq!(select.highest_modseq, None);
+    }
+
+    #[test]
+    fn gmail_fetch_with_literal_and_nil() {
+        let response = b"* 2 EXISTS\r\n\
+* 1 FETCH (X-GM-THRID 1278455344230334865 X-GM-MSGID 1278455344230334866 UID 10 X-GM-LABELS (\\Inbox \"\\\\Important\" \"Work/Q&AOQ-\"))\r\n\
+* 2 FETCH (X-GM-THRID NIL UID 11 X-GM-LABELS ({6}\r\nTo do! \"Trips\"))\r\n\
+* 3 FETCH (FLAGS (\\Seen))\r\n\
+a4 OK Success\r\n";
+        let attrs = parse_gmail_fetch(response);
+        assert_eq!(attrs.len(), 2);
+
+        assert_eq!(attrs[0].uid, 10);
+        assert_eq!(attrs[0].thread_id, Some(1278455344230334865));
+        assert_eq!(attrs[0].message_id, Some(1278455344230334866));
+        assert_eq!(attrs[0].labels, ["\\Inbox", "\\Important", "Work/Q&AOQ-"]);
+
+        assert_eq!(attrs[1].uid, 11);
+        assert_eq!(attrs[1].thread_id, None);
+        assert_eq!(attrs[1].message_id, None);
+        assert_eq!(attrs[1].labels, ["To do!", "Trips"]);
+    }
+
+    #[test]
+    fn status_unseen() {
+        let response = b"* STATUS \"[Gmail]/Spam\" (MESSAGES 12 UNSEEN 3)\r\na5 OK Success\r\n";
+        assert_eq!(parse_status_unseen(response), Some(3));
+
+        let literal = b"* 4 EXISTS\r\n* STATUS {8}\r\nA (B) C! (UNSEEN 0)\r\na6 OK Success\r\n";
+        assert_eq!(parse_status_unseen(literal), Some(0));
+
+        assert_eq!(parse_status_unseen(b"a7 NO [NONEXISTENT] Unknown Mailbox\r\n"), None);
+    }
+}
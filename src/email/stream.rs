@@ -0,0 +1,151 @@
+//! A connection the `imap` crate and raw commands can take turns on.
+//!
+//! The `imap` crate reads every response line through imap-proto and gives
+//! up on the first one it does not know, such as Gmail's X-GM-THRID, leaving
+//! the rest of the response unread. Commands answered with those go around
+//! it, straight to the stream both share.
+
+use anyhow::{bail, Result};
+use native_tls::TlsStream;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use imap::extensions::idle::SetReadTimeout;
+
+#[derive(Clone)]
+pub struct SharedStream(Arc<Mutex<TlsStream<TcpStream>>>);
+
+impl SharedStream {
+    pub fn new(stream: TlsStream<TcpStream>) -> Self {
+        Self(Arc::new(Mutex::new(stream)))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, TlsStream<TcpStream>> {
+        // A panic while holding the lock leaves nothing half-updated on our side
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Send `command` under `tag` and read up to and including the tagged
+    /// reply. Fails unless the reply is OK. Must only be called between the
+    /// `imap` crate's own commands, when nothing else is left unread.
+    pub fn run(&self, tag: &str, command: &str) -> Result<Vec<u8>> {
+        let mut stream = self.lock();
+        stream.write_all(format!("{} {}\r\n", tag, command).as_bytes())?;
+        stream.flush()?;
+
+        let mut response = Vec::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            if let Some(status) = tagged_status(&response, tag) {
+                if !status.starts_with("OK") {
+                    bail!("{} failed: {}", command, status);
+                }
+                return Ok(response);
+            }
+            let n = stream.read(&mut buf)?;
+            if n == 0 {
+                return Err(imap::Error::ConnectionLost.into());
+            }
+            response.extend_from_slice(&buf[..n]);
+        }
+    }
+}
+
+impl Read for SharedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.lock().read(buf)
+    }
+}
+
+impl Write for SharedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.lock().flush()
+    }
+}
+
+impl SetReadTimeout for SharedStream {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> imap::Result<()> {
+        self.lock().get_ref().set_read_timeout(timeout)?;
+        Ok(())
+    }
+}
+
+/// Read the greeting and ask to switch to TLS, leaving `tcp` ready for the
+/// handshake. The server sends nothing past its reply until then, so no
+/// bytes are lost to the buffer.
+pub fn starttls(tcp: &TcpStream) -> Result<()> {
+    let mut reader = BufReader::new(tcp);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    (&*tcp).write_all(b"s1 STARTTLS\r\n")?;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(imap::Error::ConnectionLost.into());
+        }
+        if let Some(status) = line.strip_prefix("s1 ") {
+            if !status.starts_with("OK") {
+                bail!("STARTTLS failed: {}", status.trim_end());
+            }
+            return Ok(());
+        }
+    }
+}
+
+/// The rest of the tagged line once `response` holds all of it, e.g.
+/// "OK Success" for `x1 OK Success`. Literals are skipped, so their content
+/// is never taken for a line.
+fn tagged_status(response: &[u8], tag: &str) -> Option<String> {
+    let mut pos = 0;
+    while pos < response.len() {
+        let end = pos + response[pos..].iter().position(|&c| c == b'\n')?;
+        let line = &response[pos..end];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        if let Some(rest) = line.strip_prefix(tag.as_bytes()).and_then(|r| r.strip_prefix(b" ")) {
+            return Some(String::from_utf8_lossy(rest).into_owned());
+        }
+        pos = end + 1;
+
+        // `{n}` at the end of a line announces n bytes of literal
+        if let Some(open) = line.strip_suffix(b"}").and_then(|l| l.iter().rposition(|&c| c == b'{')) {
+            let len = std::str::from_utf8(&line[open + 1..line.len() - 1])
+                .ok()
+                .and_then(|n| n.parse::<usize>().ok());
+            if let Some(len) = len {
+                pos += len;
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tagged_line_after_untagged_ones() {
+        let response = b"* 1 EXISTS\r\n* OK [HIGHESTMODSEQ 9]\r\nx1 OK Success\r\n";
+        assert_eq!(tagged_status(response, "x1").as_deref(), Some("OK Success"));
+    }
+
+    #[test]
+    fn literal_content_is_not_a_line() {
+        let response = b"* 1 FETCH (UID 7 X-GM-LABELS ({12}\r\nx1 OK fake\r\n))\r\nx1 NO Failure\r\n";
+        assert_eq!(tagged_status(response, "x1").as_deref(), Some("NO Failure"));
+    }
+
+    #[test]
+    fn incomplete_response() {
+        assert_eq!(tagged_status(b"* 1 EXISTS\r\nx1 OK Succ", "x1"), None);
+        assert_eq!(tagged_status(b"* 1 FETCH (BODY[] {20}\r\nx1 OK fake\r\n", "x1"), None);
+        assert_eq!(tagged_status(b"x10 OK Success\r\n", "x1"), None);
+    }
+}
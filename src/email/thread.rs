@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use super::Email;
+
+/// Group `emails` into conversations. Returns, for each email, the index of
+/// the first email of its conversation, which is the newest as lists are
+/// kept newest first.
+///
+/// Messages share a conversation when Gmail gave them the same X-GM-THRID,
+/// or when they are linked through Message-ID, In-Reply-To and References.
+/// Both are only compared within one account.
+pub fn conversations(emails: &[Email]) -> Vec<usize> {
+    let mut parent: Vec<usize> = (0..emails.len()).collect();
+    let mut threads: HashMap<(usize, u64), usize> = HashMap::new();
+    let mut message_ids: HashMap<(usize, &str), usize> = HashMap::new();
+
+    for (i, email) in emails.iter().enumerate() {
+        if let Some(thread_id) = email.thread_id {
+            let first = *threads.entry((email.account, thread_id)).or_insert(i);
+            union(&mut parent, first, i);
+        }
+        let ids = email
+            .message_id
+            .iter()
+            .chain(email.in_reply_to.iter())
+            .chain(email.references.iter());
+        for id in ids {
+            let first = *message_ids.entry((email.account, id.as_str())).or_insert(i);
+            union(&mut parent, first, i);
+        }
+    }
+
+    (0..emails.len()).map(|i| find(&mut parent, i)).collect()
+}
+
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// Join two groups under the lower index, so every group is named after
+/// its first member
+fn union(parent: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parent, a), find(parent, b));
+    if a != b {
+        parent[a.max(b)] = a.min(b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(account: usize, message_id: &str) -> Email {
+        Email {
+            account,
+            folder: "INBOX".to_string(),
+            uid: 0,
+            subject: String::new(),
+            from: String::new(),
+            from_address: String::new(),
+            date: None,
+            body: String::new(),
+            body_loaded: false,
+            body_section: None,
+            has_attachments: false,
+            seen: true,
+            flagged: false,
+            important: false,
+            message_id: Some(message_id.to_string()),
+            in_reply_to: None,
+            references: Vec::new(),
+            thread_id: None,
+            gmail_id: None,
+            labels: Vec::new(),
+        }
+    }
+
+    fn reply(account: usize, message_id: &str, to: &str, references: &[&str]) -> Email {
+        Email {
+            in_reply_to: Some(to.to_string()),
+            references: references.iter().map(|r| r.to_string()).collect(),
+            ..email(account, message_id)
+        }
+    }
+
+    fn in_thread(account: usize, message_id: &str, thread_id: u64) -> Email {
+        Email {
+            thread_id: Some(thread_id),
+            ..email(account, message_id)
+        }
+    }
+
+    #[test]
+    fn gmail_thread_ids() {
+        let emails = [in_thread(0, "a", 7), in_thread(0, "b", 8), in_thread(0, "c", 7)];
+        assert_eq!(conversations(&emails), [0, 1, 0]);
+    }
+
+    #[test]
+    fn reply_chain_joins_unrelated_messages() {
+        // 1 and 2 have nothing in common until 3 names both
+        let emails = [
+            email(0, "other"),
+            email(0, "a"),
+            email(0, "b"),
+            reply(0, "c", "b", &["a", "b"]),
+        ];
+        assert_eq!(conversations(&emails), [0, 1, 1, 1]);
+    }
+
+    #[test]
+    fn transitive_through_a_later_link() {
+        // 0 replies to 2, and 1 replies to 3, which 2 references
+        let emails = [
+            reply(0, "d", "b", &["b"]),
+            reply(0, "e", "c", &[]),
+            reply(0, "b", "a", &["a", "c"]),
+            email(0, "c"),
+        ];
+        assert_eq!(conversations(&emails), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn accounts_kept_apart() {
+        let emails = [
+            in_thread(0, "a", 7),
+            in_thread(1, "b", 7),
+            reply(1, "c", "a", &["a"]),
+            email(0, "a"),
+        ];
+        assert_eq!(conversations(&emails), [0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn root_is_the_newest() {
+        let emails = [
+            email(0, "x"),
+            reply(0, "b", "a", &["a"]),
+            email(0, "y"),
+            email(0, "a"),
+            reply(0, "c", "b", &["a", "b"]),
+        ];
+        let roots = conversations(&emails);
+        assert_eq!(roots, [0, 1, 2, 1, 1]);
+        for (i, &root) in roots.iter().enumerate() {
+            assert!(root <= i);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! IMAP's modified UTF-7 (RFC 3501, 5.1.3), which mailbox names are in.
+
+/// The readable form of a mailbox name, e.g. "Entw&APw-rfe" for "Entwürfe".
+/// Anything malformed is left as it is.
+pub fn decode(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut rest = name;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        let shifted = &rest[start + 1..];
+        let Some(end) = shifted.find('-') else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+        let encoded = &shifted[..end];
+        if encoded.is_empty() {
+            out.push('&');
+        } else {
+            match decode_shifted(encoded) {
+                Some(text) => out.push_str(&text),
+                None => out.push_str(&rest[start..start + end + 2]),
+            }
+        }
+        rest = &shifted[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The mailbox name for `name` as typed
+pub fn encode(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut shifted: Vec<u16> = Vec::new();
+    for c in name.chars() {
+        if (' '..='~').contains(&c) {
+            if !shifted.is_empty() {
+                encode_shifted(&shifted, &mut out);
+                shifted.clear();
+            }
+            if c == '&' {
+                out.push_str("&-");
+            } else {
+                out.push(c);
+            }
+        } else {
+            shifted.extend(c.encode_utf16(&mut [0; 2]).iter());
+        }
+    }
+    if !shifted.is_empty() {
+        encode_shifted(&shifted, &mut out);
+    }
+    out
+}
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+,";
+
+fn encode_shifted(units: &[u16], out: &mut String) {
+    out.push('&');
+    let mut bits: u32 = 0;
+    let mut count = 0;
+    for &unit in units {
+        bits = (bits << 16) | u32::from(unit);
+        count += 16;
+        while count >= 6 {
+            count -= 6;
+            out.push(ALPHABET[((bits >> count) & 0x3f) as usize] as char);
+        }
+        bits &= (1 << count) - 1;
+    }
+    if count > 0 {
+        out.push(ALPHABET[((bits << (6 - count)) & 0x3f) as usize] as char);
+    }
+    out.push('-');
+}
+
+/// Base64 with ',' for '/' and no padding, over UTF-16BE
+fn decode_shifted(encoded: &str) -> Option<String> {
+    let mut bits: u32 = 0;
+    let mut count = 0;
+    let mut units = Vec::new();
+    for c in encoded.bytes() {
+        let value = ALPHABET.iter().position(|&a| a == c)? as u32;
+        bits = (bits << 6) | value;
+        count += 6;
+        if count >= 16 {
+            count -= 16;
+            units.push((bits >> count) as u16);
+            bits &= (1 << count) - 1;
+        }
+    }
+    String::from_utf16(&units).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(encoded: &str, decoded: &str) {
+        assert_eq!(decode(encoded), decoded);
+        assert_eq!(encode(decoded), encoded);
+    }
+
+    #[test]
+    fn shifted() {
+        round_trip("Entw&APw-rfe", "Entwürfe");
+        round_trip("&ZeVnLIqe-", "日本語");
+        round_trip("[Gmail]/&BBoEPgRABDcEOAQ9BDA-", "[Gmail]/Корзина");
+    }
+
+    #[test]
+    fn ampersand() {
+        round_trip("&-", "&");
+        round_trip("Q&-A", "Q&A");
+    }
+
+    #[test]
+    fn ascii_unchanged() {
+        round_trip("INBOX", "INBOX");
+        round_trip("[Gmail]/Sent Mail", "[Gmail]/Sent Mail");
+    }
+
+    #[test]
+    fn malformed_left_alone() {
+        assert_eq!(decode("a&b"), "a&b");
+        assert_eq!(decode("&!!-"), "&!!-");
+    }
+}
@@ -1,8 +1,11 @@
 mod auth;
+mod cache;
 mod config;
 mod email;
 mod ui;
+mod outbox;
 mod reminders;
+mod worker;
 
 use anyhow::Result;
 use crossterm::{
@@ -11,13 +14,30 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
+use std::collections::BTreeMap;
 use std::io;
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
 
 use auth::GoogleAuth;
-use config::Config;
-use email::ImapClient;
-use ui::{handle_key_event, App};
-use reminders::RemindersFile;
+use config::{AccountConfig, AuthMethod, Config};
+use email::{utf7, Email, MessageKey, MessageRef, ReminderOutcome};
+use ui::{
+    app::{ComposeState, Folder, JournalEntry, PendingSend, View},
+    handle_key_event,
+    keybindings::Action,
+    App,
+};
+use outbox::Outbox;
+use reminders::{ReminderKind, RemindersFile};
+use worker::{BatchResult, Request, Response, Worker, WorkerEvent};
+
+/// How long to wait for a key before checking on the workers
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Messages below the open one whose bodies are fetched ahead of time
+const PREFETCH: usize = 3;
+/// How often to look for reminders and queued messages that are due
+const REMINDER_INTERVAL: Duration = Duration::from_secs(60);
 
 fn main() -> Result<()> {
     let mut config = match Config::load() {
@@ -28,44 +48,25 @@ fn main() -> Result<()> {
         }
     };
 
-    let access_token = if let Some(ref token) = config.access_token {
-        token.clone()
-    } else {
-        println!("No access token found. Starting OAuth flow...");
-        let auth = GoogleAuth::new(&config)?;
-        let (access_token, refresh_token) = auth.authenticate()?;
-        config.access_token = Some(access_token.clone());
-        config.refresh_token = Some(refresh_token);
-        config.save()?;
-        println!("Authentication successful!");
-        access_token
-    };
-
-    println!("Connecting to Gmail...");
-    let mut imap_client = match ImapClient::connect(&config.email, &access_token) {
-        Ok(client) => client,
-        Err(_) => {
-            if let Some(ref refresh_token) = config.refresh_token {
-                println!("Access token expired, refreshing...");
-                let auth = GoogleAuth::new(&config)?;
-                let new_token = auth.refresh_token(refresh_token)?;
-                config.access_token = Some(new_token.clone());
-                config.save()?;
-                ImapClient::connect(&config.email, &new_token)?
-            } else {
-                println!("Re-authenticating...");
-                let auth = GoogleAuth::new(&config)?;
-                let (access_token, refresh_token) = auth.authenticate()?;
-                config.access_token = Some(access_token.clone());
-                config.refresh_token = Some(refresh_token);
-                config.save()?;
-                ImapClient::connect(&config.email, &access_token)?
-            }
+    // Sign-in needs the terminal, so it happens before the UI starts.
+    // Everything else connects in the background.
+    for idx in 0..config.accounts.len() {
+        let account = &mut config.accounts[idx];
+        if account.auth == AuthMethod::Xoauth2 && account.access_token.is_none() {
+            println!("No access token found for {}. Starting OAuth flow...", account.email);
+            authorize(account)?;
+            config.save()?;
+            println!("Authentication successful!");
         }
-    };
+    }
 
-    println!("Fetching emails...");
-    let emails = imap_client.fetch_emails("INBOX", 0, 50)?;
+    let (event_tx, events) = mpsc::channel();
+    let workers: Vec<Worker> = config
+        .accounts
+        .iter()
+        .enumerate()
+        .map(|(idx, account)| Worker::spawn(idx, account.clone(), config.page_size, event_tx.clone()))
+        .collect();
 
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -73,223 +74,872 @@ fn main() -> Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new();
-    app.set_emails(emails);
+    let account_names = config
+        .accounts
+        .iter()
+        .map(|a| a.display_name().to_string())
+        .collect();
+    let mut app = App::new(account_names);
+    app.snooze = config.snooze.clone();
+    request_folder(&mut app, &workers, &Folder::Inbox, "Loading Inbox");
+    list_folders(&mut app, &workers);
 
     let mut reminders = RemindersFile::load().unwrap_or_default();
-    let result = run_app(&mut terminal, &mut app, &mut imap_client, &config, &mut reminders);
+    app.set_reminders(reminders.reminders.clone());
+    let mut outbox = Outbox::load().unwrap_or_default();
+    app.set_outbox(outbox.messages.clone());
+    let result = run_app(
+        &mut terminal,
+        &mut app,
+        &mut config,
+        &workers,
+        &events,
+        &mut reminders,
+        &mut outbox,
+    );
 
     disable_raw_mode()?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
 
-    let _ = imap_client.logout();
-
     result
 }
 
+fn authorize(account: &mut AccountConfig) -> Result<()> {
+    let auth = GoogleAuth::new(account)?;
+    let (access_token, refresh_token) = auth.authenticate()?;
+    account.access_token = Some(access_token);
+    account.refresh_token = Some(refresh_token);
+    Ok(())
+}
+
+/// Ask the workers for a folder listing: the active account's folder, or
+/// every account's inbox for the unified view.
+fn request_folder(app: &mut App, workers: &[Worker], folder: &Folder, label: &str) {
+    // Search results come from the active account's search, not a folder
+    if let Folder::Search(query) = folder {
+        app.begin_task(app.active_account, "Searching");
+        workers[app.active_account].send(Request::Search {
+            query: query.clone(),
+            start: 0,
+        });
+        return;
+    }
+    let accounts: Vec<usize> = if *folder == Folder::AllInboxes {
+        (0..workers.len()).collect()
+    } else {
+        vec![app.active_account]
+    };
+    for account in accounts {
+        let request = match (app.folder_name(account, folder), folder.special_use()) {
+            (Some(name), _) => {
+                workers[account].watch(&name);
+                Request::FetchFolder { folder: name }
+            }
+            // LIST has not answered yet, the worker looks it up itself
+            (None, Some(special)) => Request::FetchSpecial { special },
+            (None, None) => continue,
+        };
+        app.begin_task(account, label);
+        workers[account].send(request);
+    }
+}
+
+/// Ask every account for its folders and their unread counts
+fn list_folders(app: &mut App, workers: &[Worker]) {
+    for (idx, worker) in workers.iter().enumerate() {
+        app.begin_task(idx, "Loading folders");
+        worker.send(Request::ListFolders);
+    }
+}
+
+/// Group emails by owning account as (folder, uid) pairs
+fn targets_by_account(app: &App, keys: &[MessageKey]) -> BTreeMap<usize, Vec<(String, u32)>> {
+    let mut targets: BTreeMap<usize, Vec<(String, u32)>> = BTreeMap::new();
+    for key in keys {
+        if let Some(email) = app.find_email(key) {
+            targets
+                .entry(key.account)
+                .or_default()
+                .push((email.folder.clone(), key.uid));
+        }
+    }
+    targets
+}
+
 fn run_app(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     app: &mut App,
-    imap_client: &mut ImapClient,
-    config: &Config,
+    config: &mut Config,
+    workers: &[Worker],
+    events: &Receiver<WorkerEvent>,
     reminders: &mut RemindersFile,
+    outbox: &mut Outbox,
 ) -> Result<()> {
+    let mut next_reminder_check = Instant::now();
     loop {
         let view_height = terminal.size()?.height.saturating_sub(8);
 
         terminal.draw(|f| app.render(f))?;
 
-        if let Event::Key(key) = event::read()? {
-            let action = handle_key_event(app, key, view_height);
+        if event::poll(POLL_INTERVAL)? {
+            if let Event::Key(key) = event::read()? {
+                let action = handle_key_event(app, key, view_height);
+                handle_action(app, action, config, workers, reminders, outbox);
+            }
+        }
 
-            match action {
-                ui::keybindings::Action::Refresh => {
-                    app.notify("Refreshing...");
-                    terminal.draw(|f| app.render(f))?;
+        while let Ok(event) = events.try_recv() {
+            handle_worker_event(app, config, reminders, outbox, event);
+        }
 
-                    match imap_client.fetch_emails(app.current_folder.imap_name(), 0, 50) {
-                        Ok(emails) => {
-                            app.set_emails(emails);
-                            app.notify("Refreshed");
-                        }
-                        Err(e) => {
-                            app.notify_error(&format!("Error: {}", e));
-                        }
-                    }
-                }
-                ui::keybindings::Action::ChangeFolder(folder) => {
-                    app.notify(&format!("Loading {}...", folder.display_name()));
-                    terminal.draw(|f| app.render(f))?;
-
-                    match imap_client.fetch_emails(folder.imap_name(), 0, 50) {
-                        Ok(emails) => {
-                            app.current_folder = folder;
-                            app.set_emails(emails);
-                            app.clear_notification();
-                        }
-                        Err(e) => {
-                            app.notify_error(&format!("Error: {}", e));
-                        }
-                    }
+        if Instant::now() >= next_reminder_check {
+            resurface_due(app, config, workers, reminders);
+            send_due(app, config, workers, outbox);
+            next_reminder_check = Instant::now() + REMINDER_INTERVAL;
+        }
+
+        if app.view == View::EmailView {
+            request_bodies(app, workers);
+        }
+
+        // Once the undo window is over, or right away when quitting
+        let send_now = app
+            .pending_send
+            .as_ref()
+            .is_some_and(|p| app.should_quit || Instant::now() >= p.send_at);
+        if send_now {
+            if let Some(pending) = app.pending_send.take() {
+                dispatch_send(app, workers, outbox, pending.compose);
+            }
+        }
+
+        // A message on its way would die with the process, so wait for it
+        if app.should_quit && !app.tasks.iter().any(|t| t.label == "Sending") {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Fetch the body of the open message and the next few, unless they are
+/// loaded or on their way already
+fn request_bodies(app: &mut App, workers: &[Worker]) {
+    let wanted: Vec<(MessageKey, Option<String>)> = app
+        .upcoming_emails(PREFETCH + 1)
+        .into_iter()
+        .filter(|e| !e.body_loaded && !app.body_requests.contains(&e.key()))
+        .map(|e| (e.key(), e.body_section.clone()))
+        .collect();
+
+    for (key, section) in wanted {
+        app.begin_task(key.account, "Loading message");
+        workers[key.account].send(Request::FetchBody {
+            folder: key.folder.clone(),
+            uid: key.uid,
+            section,
+        });
+        app.body_requests.insert(key);
+    }
+}
+
+/// Hand reminders that are due to the worker of their account, which moves
+/// the message back to the inbox
+fn resurface_due(app: &mut App, config: &Config, workers: &[Worker], reminders: &mut RemindersFile) {
+    let mut dropped = false;
+    for reminder in reminders.get_due_reminders() {
+        if app.resurfacing.contains(&reminder.id) {
+            continue;
+        }
+        let account = config.accounts.iter().position(|a| a.email == reminder.account);
+        let identified = reminder.message_id.is_some() || reminder.gmail_id.is_some();
+        match account {
+            Some(account) if identified => {
+                app.resurfacing.insert(reminder.id);
+                app.begin_task(account, "Resurfacing");
+                workers[account].send(Request::Resurface {
+                    id: reminder.id,
+                    message_id: reminder.message_id,
+                    gmail_id: reminder.gmail_id,
+                    follow_up: reminder.kind == ReminderKind::FollowUp,
+                });
+            }
+            _ => {
+                // Saved by an older version, or for an account that is gone
+                reminders.remove_reminder(reminder.id);
+                dropped = true;
+                app.notify_error(&format!("Reminder for \"{}\" could not be resurfaced", reminder.subject));
+            }
+        }
+    }
+    if dropped {
+        save_reminders(app, reminders);
+    }
+}
+
+/// Hand a composed message to SMTP
+fn dispatch_send(app: &mut App, workers: &[Worker], outbox: &mut Outbox, compose: ComposeState) {
+    // Sent now instead of at its time
+    if let Some(id) = compose.queued {
+        outbox.remove(id);
+        save_outbox(app, outbox);
+    }
+    let account = compose.account;
+    app.begin_task(account, "Sending");
+    workers[account].send(Request::Send {
+        email: compose.to_outgoing(),
+        queued: None,
+    });
+}
+
+/// Hand queued messages that are due to SMTP. They leave the outbox once
+/// sent, and are tried again on the next check if sending fails.
+fn send_due(app: &mut App, config: &Config, workers: &[Worker], outbox: &Outbox) {
+    for queued in outbox.get_due() {
+        let editing = (app.view == View::Compose && app.compose.queued == Some(queued.id))
+            || app.pending_send.as_ref().is_some_and(|p| p.compose.queued == Some(queued.id));
+        if editing || app.sending.contains(&queued.id) {
+            continue;
+        }
+        match config.accounts.iter().position(|a| a.email == queued.account) {
+            Some(account) => {
+                app.sending.insert(queued.id);
+                app.begin_task(account, "Sending");
+                workers[account].send(Request::Send {
+                    email: queued.email,
+                    queued: Some(queued.id),
+                });
+            }
+            None => app.notify_error(&format!(
+                "\"{}\" is queued for {}, which is not configured",
+                queued.email.subject, queued.account
+            )),
+        }
+    }
+}
+
+/// Write the outbox to disk and refresh the outbox view
+fn save_outbox(app: &mut App, outbox: &Outbox) {
+    if let Err(e) = outbox.save() {
+        app.notify_error(&format!("Failed to save outbox: {}", e));
+    }
+    app.set_outbox(outbox.messages.clone());
+}
+
+/// Write reminders to disk and refresh the reminders view
+fn save_reminders(app: &mut App, reminders: &RemindersFile) {
+    if let Err(e) = reminders.save() {
+        app.notify_error(&format!("Failed to save reminders: {}", e));
+    }
+    app.set_reminders(reminders.reminders.clone());
+}
+
+fn handle_action(
+    app: &mut App,
+    action: Action,
+    config: &Config,
+    workers: &[Worker],
+    reminders: &mut RemindersFile,
+    outbox: &mut Outbox,
+) {
+    match action {
+        Action::Refresh => {
+            let folder = app.current_folder.clone();
+            request_folder(app, workers, &folder, "Refreshing");
+            if app.sidebar.visible {
+                list_folders(app, workers);
+            }
+        }
+        Action::ListFolders => list_folders(app, workers),
+        Action::LoadMore => {
+            if app.paging.pending == 0 && !app.paging.exhausted {
+                let accounts: Vec<usize> = if app.current_folder == Folder::AllInboxes {
+                    (0..workers.len()).collect()
+                } else {
+                    vec![app.active_account]
+                };
+                for account in accounts {
+                    let start = app.emails.iter().filter(|e| e.account == account).count() as u32;
+                    let request = match &app.current_folder {
+                        Folder::Search(query) => Request::Search {
+                            query: query.clone(),
+                            start,
+                        },
+                        folder => match app.folder_name(account, folder) {
+                            Some(folder) => Request::FetchMore { folder, start },
+                            None if *folder == Folder::Starred => Request::FetchFlagged { start },
+                            None => continue,
+                        },
+                    };
+                    app.begin_task(account, "Loading more");
+                    app.paging.pending += 1;
+                    workers[account].send(request);
                 }
-                ui::keybindings::Action::SendEmail => {
-                    if app.compose.to.is_empty() {
-                        app.notify_error("'To' field is empty");
-                    } else {
-                        app.notify("Sending...");
-                        terminal.draw(|f| app.render(f))?;
-
-                        let access_token = config.access_token.as_ref().unwrap();
-                        match email::send_email(
-                            &config.email,
-                            &app.compose.to,
-                            &app.compose.cc,
-                            &app.compose.subject,
-                            &app.compose.body,
-                            access_token,
-                            app.compose.in_reply_to.as_deref(),
-                            &app.compose.references,
-                        ) {
-                            Ok(_) => {
-                                app.notify("Sent");
-                                app.view = ui::app::View::Inbox;
-                                app.compose = Default::default();
-                            }
-                            Err(e) => {
-                                app.notify_error(&format!("Send failed: {}", e));
-                            }
-                        }
-                    }
+            }
+        }
+        Action::ChangeFolder(folder) => {
+            let label = format!("Loading {}", folder.display_name());
+            app.current_folder = folder.clone();
+            app.set_emails(Vec::new());
+            request_folder(app, workers, &folder, &label);
+        }
+        Action::SendEmail => {
+            if app.compose.to.is_empty() {
+                app.notify_error("'To' field is empty");
+            } else {
+                // Only one message is held back at a time
+                if let Some(pending) = app.pending_send.take() {
+                    dispatch_send(app, workers, outbox, pending.compose);
                 }
-                ui::keybindings::Action::SaveDraft => {
-                    if app.compose.to.is_empty() && app.compose.cc.is_empty() {
-                        app.notify_error("'To' or 'Cc' field is required");
-                    } else {
-                        app.notify("Saving draft...");
-                        terminal.draw(|f| app.render(f))?;
-
-                        match imap_client.save_draft(
-                            &config.email,
-                            &app.compose.to,
-                            &app.compose.cc,
-                            &app.compose.subject,
-                            &app.compose.body,
-                        ) {
-                            Ok(_) => {
-                                app.notify("Draft saved");
-                                app.view = ui::app::View::Inbox;
-                                app.compose = Default::default();
-                            }
-                            Err(e) => {
-                                app.notify_error(&format!("Draft failed: {}", e));
-                            }
-                        }
-                    }
+                let compose = std::mem::take(&mut app.compose);
+                if config.undo_send == 0 {
+                    dispatch_send(app, workers, outbox, compose);
+                } else {
+                    app.pending_send = Some(PendingSend {
+                        compose,
+                        send_at: Instant::now() + Duration::from_secs(config.undo_send),
+                    });
                 }
-                ui::keybindings::Action::EditDraft => {
-                    app.edit_draft();
+                app.view = View::Inbox;
+            }
+        }
+        Action::SendLater(when) => match reminders::calculate_return_time(&when, &config.snooze) {
+            Ok(send_at) => {
+                let email = app.compose.to_outgoing();
+                let edited = app.compose.queued.is_some_and(|id| outbox.update(id, email.clone(), send_at));
+                if !edited {
+                    outbox.add(&config.accounts[app.compose.account].email, email, send_at);
                 }
-                ui::keybindings::Action::ArchiveEmail => {
-                    let uids: Vec<u32> = if app.selected.is_empty() {
-                        app.selected_email().map(|e| e.uid).into_iter().collect()
-                    } else {
-                        app.get_selected_uids()
+                save_outbox(app, outbox);
+                let local = send_at.with_timezone(&chrono::Local);
+                app.notify(&format!("Scheduled for {}", local.format("%a, %b %d %H:%M")));
+                app.view = if edited { View::Outbox } else { View::Inbox };
+                app.compose = Default::default();
+            }
+            Err(e) => {
+                app.view = View::Compose;
+                app.notify_error(&format!("Invalid time: {}", e));
+            }
+        },
+        Action::EditQueued(id) => {
+            let Some(queued) = outbox.messages.iter().find(|m| m.id == id) else {
+                return;
+            };
+            match config.accounts.iter().position(|a| a.email == queued.account) {
+                Some(account) => {
+                    app.compose = ComposeState {
+                        queued: Some(id),
+                        ..ComposeState::from_outgoing(account, queued.email.clone())
                     };
-                    
-                    if !uids.is_empty() {
-                        let count = uids.len();
-                        app.notify(&format!("Archiving {}...", count));
-                        terminal.draw(|f| app.render(f))?;
-
-                        let mut success = 0;
-                        for uid in &uids {
-                            if imap_client.archive_email(*uid).is_ok() {
-                                success += 1;
-                            }
-                        }
-                        
-                        app.emails.retain(|e| !uids.contains(&e.uid));
-                        app.clear_selection();
-                        if app.list_state.selected().unwrap_or(0) >= app.emails.len() && !app.emails.is_empty() {
-                            app.list_state.select(Some(app.emails.len() - 1));
-                        }
-                        app.notify(&format!("Archived {}", success));
-                    }
+                    app.view = View::Compose;
+                }
+                None => app.notify_error(&format!("{} is not configured", queued.account)),
+            }
+        }
+        Action::RescheduleQueued(id, when) => match reminders::calculate_return_time(&when, &config.snooze) {
+            Ok(send_at) => {
+                if outbox.reschedule(id, send_at) {
+                    save_outbox(app, outbox);
+                    let local = send_at.with_timezone(&chrono::Local);
+                    app.notify(&format!("Moved to {}", local.format("%a, %b %d %H:%M")));
                 }
-                ui::keybindings::Action::DeleteEmail => {
-                    let uids: Vec<u32> = if app.selected.is_empty() {
-                        app.selected_email().map(|e| e.uid).into_iter().collect()
+            }
+            Err(e) => {
+                app.notify_error(&format!("Invalid time: {}", e));
+            }
+        },
+        Action::CancelQueued(id) => {
+            if app.sending.contains(&id) {
+                app.notify_error("Already on its way");
+            } else if outbox.remove(id).is_some() {
+                save_outbox(app, outbox);
+                app.notify("Scheduled message cancelled");
+            }
+        }
+        Action::SaveDraft => {
+            if app.compose.to.is_empty() && app.compose.cc.is_empty() {
+                app.notify_error("'To' or 'Cc' field is required");
+            } else {
+                let account = app.compose.account;
+                app.begin_task(account, "Saving draft");
+                workers[account].send(Request::SaveDraft {
+                    email: app.compose.to_outgoing(),
+                });
+                app.view = View::Inbox;
+                app.compose = Default::default();
+            }
+        }
+        Action::SwitchAccount(idx) => {
+            if app.switch_account(idx) {
+                let folder = app.current_folder.clone();
+                let label = format!("Loading {}", app.active_account_name());
+                request_folder(app, workers, &folder, &label);
+            } else {
+                app.notify(&format!("Account: {}", app.active_account_name()));
+            }
+        }
+        Action::EditDraft => {
+            app.edit_draft();
+        }
+        Action::ArchiveEmail => {
+            let keys = app.target_keys();
+            if matches!(app.current_folder, Folder::Search(_)) {
+                record_search_archive(app, &keys);
+            } else {
+                record_removal(app, "archive", &keys);
+            }
+            for (account, targets) in targets_by_account(app, &keys) {
+                app.begin_task(account, &format!("Archiving {}", targets.len()));
+                workers[account].send(Request::Archive { targets });
+            }
+            app.remove_emails(&keys);
+            app.clear_selection();
+        }
+        Action::DeleteEmail => {
+            let keys = app.target_keys();
+            // Out of Trash they are gone for good, only the others can come back
+            let trashed: Vec<MessageKey> = keys
+                .iter()
+                .filter(|key| match app.folder_name(key.account, &Folder::Trash) {
+                    Some(trash) => key.folder != trash,
+                    None => app.current_folder != Folder::Trash,
+                })
+                .cloned()
+                .collect();
+            record_removal(app, "delete", &trashed);
+            for (account, targets) in targets_by_account(app, &keys) {
+                app.begin_task(account, &format!("Deleting {}", targets.len()));
+                workers[account].send(Request::Delete { targets });
+            }
+            app.remove_emails(&keys);
+            app.clear_selection();
+        }
+        Action::MarkAsRead(keys) => {
+            for key in keys {
+                if let Some(email) = app.emails.iter_mut().find(|e| e.key() == key) {
+                    email.seen = true;
+                    let folder = email.folder.clone();
+                    app.begin_task(key.account, "Marking read");
+                    workers[key.account].send(Request::MarkAsRead { folder, uid: key.uid });
+                }
+            }
+        }
+        Action::ToggleStar(key) => {
+            if let Some(flagged) = app.find_email(&key).map(|e| e.flagged) {
+                set_star(app, workers, &key, !flagged);
+                app.record(JournalEntry::Starred(key));
+            }
+        }
+        Action::MoveTo(to) => {
+            let keys = std::mem::take(&mut app.picker.targets);
+            let (labeled, moved): (Vec<MessageKey>, Vec<MessageKey>) =
+                keys.into_iter().partition(|key| app.move_only_labels(key, &to));
+            if !labeled.is_empty() {
+                let label = if to.eq_ignore_ascii_case("INBOX") { "\\Inbox" } else { to.as_str() };
+                label_emails(app, workers, &labeled, label, true);
+            }
+            record_removal(app, "move", &moved);
+            for (account, targets) in targets_by_account(app, &moved) {
+                app.begin_task(account, &format!("Moving {}", targets.len()));
+                workers[account].send(Request::Move { targets, to: to.clone() });
+            }
+            app.remove_emails(&moved);
+            app.clear_selection();
+        }
+        Action::ToggleLabel(label) => {
+            let keys = std::mem::take(&mut app.picker.targets);
+            let add = app.label_count(&keys, &label) < keys.len();
+            label_emails(app, workers, &keys, &label, add);
+            app.clear_selection();
+        }
+        Action::FetchThread => {
+            if let Some(email) = app.open_email().cloned() {
+                if !email.references.is_empty() || email.in_reply_to.is_some() {
+                    app.begin_task(email.account, "Loading thread");
+                    workers[email.account].send(Request::FetchThread { email });
+                }
+            }
+        }
+        Action::RemindEmail(key, duration_str) => {
+            let Some(email) = app.find_email(&key).cloned() else {
+                return;
+            };
+            if email.message_id.is_none() && email.gmail_id.is_none() {
+                app.notify_error("This message has no Message-ID, it could not be found again");
+                return;
+            }
+            match reminders::calculate_return_time(&duration_str, &config.snooze) {
+                Ok(return_time) => {
+                    let account = &config.accounts[key.account].email;
+                    reminders.add_reminder(account, &email, return_time);
+                    app.set_reminders(reminders.reminders.clone());
+                    if let Err(e) = reminders.save() {
+                        app.notify_error(&format!("Failed to save reminder: {}", e));
                     } else {
-                        app.get_selected_uids()
-                    };
-                    
-                    if !uids.is_empty() {
-                        let count = uids.len();
-                        app.notify(&format!("Deleting {}...", count));
-                        terminal.draw(|f| app.render(f))?;
-
-                        let mut success = 0;
-                        for uid in &uids {
-                            if imap_client.delete_email(*uid).is_ok() {
-                                success += 1;
-                            }
-                        }
-                        
-                        app.emails.retain(|e| !uids.contains(&e.uid));
-                        app.clear_selection();
-                        if app.list_state.selected().unwrap_or(0) >= app.emails.len() && !app.emails.is_empty() {
-                            app.list_state.select(Some(app.emails.len() - 1));
+                        let local = return_time.with_timezone(&chrono::Local);
+                        app.notify(&format!("Reminder set for {}", local.format("%a, %b %d %H:%M")));
+                        // Move email to archive
+                        for (account, targets) in targets_by_account(app, std::slice::from_ref(&key)) {
+                            app.begin_task(account, "Archiving 1");
+                            workers[account].send(Request::Archive { targets });
                         }
-                        app.notify(&format!("Deleted {}", success));
+                        app.remove_emails(&[key]);
                     }
                 }
-                ui::keybindings::Action::MarkAsRead(uid) => {
-                    let _ = imap_client.mark_as_read(uid);
-                    if let Some(idx) = app.list_state.selected() {
-                        if let Some(email) = app.emails.get_mut(idx) {
-                            email.seen = true;
-                        }
-                    }
+                Err(e) => {
+                    app.notify_error(&format!("Invalid time: {}", e));
                 }
-                ui::keybindings::Action::FetchThread => {
-                    if let Some(email) = app.selected_email().cloned() {
-                        if !email.references.is_empty() || email.in_reply_to.is_some() {
-                            if let Ok(thread) = imap_client.fetch_thread(&email) {
-                                app.set_reply_chain_from_thread(thread);
-                            }
-                        }
-                    }
+            }
+        }
+        Action::FollowUp(when) => {
+            let Some((account, email)) = app.last_sent.take() else {
+                return;
+            };
+            match reminders::calculate_return_time(&when, &config.snooze) {
+                Ok(return_time) => {
+                    reminders.add_follow_up(&config.accounts[account].email, &email, return_time);
+                    save_reminders(app, reminders);
+                    let local = return_time.with_timezone(&chrono::Local);
+                    app.notify(&format!(
+                        "Unless someone replies, reminding you on {}",
+                        local.format("%a, %b %d %H:%M")
+                    ));
                 }
-                ui::keybindings::Action::RemindEmail(uid, duration_str) => {
-                    match reminders::calculate_return_time(&duration_str) {
-                        Ok(return_time) => {
-                            reminders.add_reminder(uid, return_time);
-                            if let Err(e) = reminders.save() {
-                                app.notify_error(&format!("Failed to save reminder: {}", e));
-                            } else {
-                                let msg = format!("Email reminded for {}", duration_str);
-                                app.notify(&msg);
-                                // Move email to archive
-                                let _ = imap_client.archive_email(uid);
-                                app.emails.retain(|e| e.uid != uid);
-                                if app.list_state.selected().unwrap_or(0) >= app.emails.len() && !app.emails.is_empty() {
-                                    app.list_state.select(Some(app.emails.len() - 1));
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            app.notify_error(&format!("Invalid time format: {}", e));
-                        }
-                    }
+                Err(e) => {
+                    // Keep it, so the time can be typed again
+                    app.last_sent = Some((account, email));
+                    app.notify_error(&format!("Invalid time: {}", e));
+                }
+            }
+        }
+        Action::CancelReminder(id) => {
+            if reminders.remove_reminder(id).is_some() {
+                save_reminders(app, reminders);
+                app.notify("Reminder cancelled, the message stays archived");
+            }
+        }
+        Action::RescheduleReminder(id, when) => match reminders::calculate_return_time(&when, &config.snooze) {
+            Ok(return_time) => {
+                if reminders.reschedule(id, return_time) {
+                    save_reminders(app, reminders);
+                    let local = return_time.with_timezone(&chrono::Local);
+                    app.notify(&format!("Reminder moved to {}", local.format("%a, %b %d %H:%M")));
                 }
-                ui::keybindings::Action::None => {}
+            }
+            Err(e) => {
+                app.notify_error(&format!("Invalid time: {}", e));
+            }
+        },
+        Action::Undo => {
+            if app.pending_send.is_some() {
+                app.undo_send();
+            } else {
+                undo(app, workers);
             }
         }
+        Action::None => {}
+    }
+}
 
-        if app.should_quit {
-            break;
+/// Settle an archive or delete, naming the messages that failed
+fn report_batch(app: &mut App, account: usize, verb: &str, result: BatchResult) {
+    let failed_targets: Vec<(String, u32)> = result
+        .failed
+        .iter()
+        .map(|(folder, uid, _)| (folder.clone(), *uid))
+        .collect();
+    let failed = app.settle_removal(account, &result.done, &failed_targets);
+    let Some((_, _, error)) = result.failed.first() else {
+        app.notify(&format!("{} {}", verb, result.done.len()));
+        return;
+    };
+
+    let names: Vec<String> = result
+        .failed
+        .iter()
+        .map(|(folder, uid, _)| match failed.iter().find(|e| e.uid == *uid && e.folder == *folder) {
+            Some(email) => format!("\"{}\"", email.subject),
+            None => format!("UID {}", uid),
+        })
+        .collect();
+    app.notify_error(&format!(
+        "{} {} of {}, failed: {} ({})",
+        verb,
+        result.done.len(),
+        result.done.len() + result.failed.len(),
+        names.join(", "),
+        error
+    ));
+}
+
+fn record_removal(app: &mut App, verb: &'static str, keys: &[MessageKey]) {
+    let emails: Vec<Email> = keys.iter().filter_map(|k| app.find_email(k).cloned()).collect();
+    if !emails.is_empty() {
+        app.record(JournalEntry::Removed { verb, emails });
+    }
+}
+
+/// Put `label` on listed messages or take it off, on screen at once and
+/// on the server in the background
+fn label_emails(app: &mut App, workers: &[Worker], keys: &[MessageKey], label: &str, add: bool) {
+    app.set_label(keys, label, add);
+    for (account, targets) in targets_by_account(app, keys) {
+        let verb = if add { "Labeling" } else { "Unlabeling" };
+        app.begin_task(account, &format!("{} {}", verb, targets.len()));
+        workers[account].send(Request::Label {
+            targets,
+            label: label.to_string(),
+            add,
+        });
+    }
+}
+
+/// Journal an archive of search results. Gmail's come from All Mail and
+/// only lose the Inbox label, which undo puts back by copying them to
+/// INBOX, so they are recorded as taken from there.
+fn record_search_archive(app: &mut App, keys: &[MessageKey]) {
+    let emails: Vec<Email> = keys
+        .iter()
+        .filter_map(|k| app.find_email(k))
+        .filter(|e| e.gmail_id.is_none() || e.labels.iter().any(|l| l == "\\Inbox"))
+        .map(|e| match e.gmail_id {
+            Some(_) => Email {
+                folder: "INBOX".to_string(),
+                ..e.clone()
+            },
+            None => e.clone(),
+        })
+        .collect();
+    if !emails.is_empty() {
+        app.record(JournalEntry::Removed { verb: "archive", emails });
+    }
+}
+
+/// Star or unstar a listed message, on screen at once and on the server
+/// in the background
+fn set_star(app: &mut App, workers: &[Worker], key: &MessageKey, flagged: bool) {
+    let Some(email) = app.emails.iter_mut().find(|e| e.key() == *key) else {
+        return;
+    };
+    email.flagged = flagged;
+    app.begin_task(key.account, if flagged { "Starring" } else { "Unstarring" });
+    workers[key.account].send(Request::Star {
+        folder: key.folder.clone(),
+        uid: key.uid,
+        flagged,
+    });
+}
+
+/// Take back the latest operation in the journal
+fn undo(app: &mut App, workers: &[Worker]) {
+    let Some(entry) = app.journal.pop() else {
+        app.notify("Nothing to undo");
+        return;
+    };
+    match entry {
+        JournalEntry::Starred(key) => {
+            if let Some(flagged) = app.find_email(&key).map(|e| e.flagged) {
+                set_star(app, workers, &key, !flagged);
+                app.notify("Star undone");
+            }
         }
+        JournalEntry::Removed { verb, emails } => {
+            let total = emails.len();
+            let mut by_folder: BTreeMap<(usize, String), Vec<MessageRef>> = BTreeMap::new();
+            for email in emails {
+                if email.message_id.is_some() || email.gmail_id.is_some() {
+                    by_folder
+                        .entry((email.account, email.folder))
+                        .or_default()
+                        .push((email.message_id, email.gmail_id));
+                }
+            }
+            let findable: usize = by_folder.values().map(Vec::len).sum();
+            if findable < total {
+                app.notify_error(&format!(
+                    "{} of {} have no Message-ID and cannot be found again",
+                    total - findable,
+                    total
+                ));
+            }
+            if by_folder.is_empty() {
+                return;
+            }
+            for ((account, folder), messages) in by_folder {
+                app.begin_task(account, &format!("Undoing {}", verb));
+                workers[account].send(Request::Restore { folder, messages });
+            }
+            // Queued behind the restores, so the list shows them back
+            let folder = app.current_folder.clone();
+            request_folder(app, workers, &folder, "Refreshing");
+        }
+    }
+}
+
+fn handle_worker_event(
+    app: &mut App,
+    config: &mut Config,
+    reminders: &mut RemindersFile,
+    outbox: &mut Outbox,
+    event: WorkerEvent,
+) {
+    if event.response.completes_task() {
+        app.finish_task(event.account);
     }
 
-    Ok(())
+    match event.response {
+        Response::Cached { folder, emails } | Response::Emails { folder, emails } => {
+            app.accept_emails(event.account, &folder, emails);
+        }
+        Response::Found { query, start, emails } => {
+            app.accept_found(event.account, &query, start, emails);
+        }
+        Response::Flagged { start, emails } => {
+            app.accept_flagged(event.account, start, emails);
+        }
+        Response::Folders { folders, gmail } => {
+            app.accounts[event.account].folders = folders;
+            app.accounts[event.account].gmail = Some(gmail);
+        }
+        Response::MoreEmails { folder, emails } => {
+            app.append_emails(event.account, &folder, emails);
+        }
+        Response::MoreFailed(error) => {
+            app.paging.pending = app.paging.pending.saturating_sub(1);
+            app.notify_error(&error);
+        }
+        Response::Body { folder, uid, body } => {
+            app.set_body(MessageKey { account: event.account, folder, uid }, body);
+        }
+        Response::BodyFailed { folder, uid, error } => {
+            app.body_requests.remove(&MessageKey { account: event.account, folder, uid });
+            app.notify_error(&format!("Could not load message: {}", error));
+        }
+        Response::TokenRefreshed(access_token) => {
+            config.accounts[event.account].access_token = Some(access_token);
+            if let Err(e) = config.save() {
+                app.notify_error(&format!("Failed to save config: {}", e));
+            }
+        }
+        Response::NewEmails { folder, emails } => {
+            let count = app.insert_new_emails(event.account, &folder, emails);
+            if count > 0 {
+                app.notify(&format!("{} new", count));
+            }
+        }
+        Response::Archived(result) => report_batch(app, event.account, "Archived", result),
+        Response::Deleted(result) => report_batch(app, event.account, "Deleted", result),
+        Response::Moved(result) => report_batch(app, event.account, "Moved", result),
+        Response::Labeled { label, add, result } => {
+            let name = utf7::decode(&label);
+            if let Some((_, _, error)) = result.failed.first() {
+                let keys: Vec<MessageKey> = result
+                    .failed
+                    .iter()
+                    .map(|(folder, uid, _)| MessageKey {
+                        account: event.account,
+                        folder: folder.clone(),
+                        uid: *uid,
+                    })
+                    .collect();
+                app.set_label(&keys, &label, !add);
+                app.notify_error(&format!("Could not change label {} on {}: {}", name, keys.len(), error));
+            } else if add {
+                app.notify(&format!("Labeled {} {}", result.done.len(), name));
+            } else {
+                app.notify(&format!("Removed {} from {}", name, result.done.len()));
+            }
+        }
+        Response::Restored { folder, restored, total } => {
+            if restored == total {
+                app.notify(&format!("Undone, {} back in {}", restored, folder));
+            } else {
+                app.notify_error(&format!("Undo: {} of {} back in {}, the rest were not found", restored, total, folder));
+            }
+        }
+        Response::MarkedAsRead | Response::Starred => {}
+        Response::StarFailed { folder, uid, flagged, error } => {
+            let key = MessageKey { account: event.account, folder, uid };
+            if let Some(email) = app.emails.iter_mut().find(|e| e.key() == key) {
+                email.flagged = !flagged;
+            }
+            app.notify_error(&format!("Could not change the star: {}", error));
+        }
+        Response::Thread(thread) => {
+            // Only if the reply that asked for it is still being written
+            let in_reply_to = app.compose.in_reply_to.clone();
+            if app.view == View::Compose
+                && in_reply_to.is_some()
+                && thread.iter().any(|e| e.message_id == in_reply_to)
+            {
+                app.set_reply_chain_from_thread(thread);
+            }
+        }
+        Response::Sent { email, queued } => {
+            if let Some(id) = queued {
+                app.sending.remove(&id);
+                outbox.remove(id);
+                save_outbox(app, outbox);
+            }
+            app.last_sent = Some((event.account, email));
+            app.notify("Sent · F to be reminded if nobody replies");
+        }
+        Response::SendFailed { email, error, queued: Some(id) } => {
+            // Stays queued, and is tried again on the next check
+            app.sending.remove(&id);
+            app.notify_error(&format!("Scheduled send of \"{}\" failed: {}", email.subject, error));
+        }
+        Response::SendFailed { email, error, queued: None } => {
+            // Not quitting with the message lost
+            app.should_quit = false;
+            app.compose = ComposeState::from_outgoing(event.account, email);
+            app.view = View::Compose;
+            app.notify_error(&format!("Send failed: {}", error));
+        }
+        Response::DraftSaved => {
+            app.notify("Draft saved");
+        }
+        Response::DraftFailed { email, error } => {
+            app.compose = ComposeState::from_outgoing(event.account, email);
+            app.view = View::Compose;
+            app.notify_error(&format!("Draft failed: {}", error));
+        }
+        Response::Resurfaced { id, outcome } => {
+            app.resurfacing.remove(&id);
+            if let Some(reminder) = reminders.remove_reminder(id) {
+                save_reminders(app, reminders);
+                if outcome == ReminderOutcome::Resurfaced {
+                    // Listed already if it was in the inbox all along
+                    for email in app.emails.iter_mut() {
+                        let same = match (reminder.gmail_id, email.gmail_id) {
+                            (Some(a), Some(b)) => a == b,
+                            _ => email.message_id.is_some() && email.message_id == reminder.message_id,
+                        };
+                        if email.account == event.account && same {
+                            email.seen = false;
+                        }
+                    }
+                    match reminder.kind {
+                        ReminderKind::Snooze => app.notify(&format!("Reminder: {}", reminder.subject)),
+                        ReminderKind::FollowUp => app.notify(&format!("No reply yet: {}", reminder.subject)),
+                    }
+                } else if outcome == ReminderOutcome::Replied {
+                    app.notify(&format!("Got a reply, reminder dropped: {}", reminder.subject));
+                } else {
+                    app.notify_error(&format!(
+                        "Reminder: \"{}\" could not be found in any folder",
+                        reminder.subject
+                    ));
+                }
+            }
+        }
+        Response::ResurfaceFailed { id, error } => {
+            // Tried again on the next check
+            app.resurfacing.remove(&id);
+            app.notify_error(&format!("Could not resurface reminder: {}", error));
+        }
+        Response::Error(message) | Response::WatchFailed(message) => {
+            app.notify_error(&message);
+        }
+    }
 }
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::email::OutgoingEmail;
+
+/// A message waiting to be sent at a set time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedEmail {
+    pub id: u64,
+    /// Address of the account it is sent from
+    pub account: String,
+    pub email: OutgoingEmail,
+    pub send_at: DateTime<Utc>,
+}
+
+/// Messages scheduled with "send later", kept on disk so they still go out
+/// after a restart
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Outbox {
+    pub messages: Vec<QueuedEmail>,
+}
+
+impl Outbox {
+    fn path() -> Result<PathBuf> {
+        let config_dir = dirs::config_dir()
+            .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?;
+        let rustmail_dir = config_dir.join("rustmail");
+        fs::create_dir_all(&rustmail_dir)?;
+        Ok(rustmail_dir.join("outbox.json"))
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path()?;
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(&path, content)?;
+        Ok(())
+    }
+
+    /// Queue `email` from the account with address `account`
+    pub fn add(&mut self, account: &str, email: OutgoingEmail, send_at: DateTime<Utc>) -> u64 {
+        let id = self.messages.iter().map(|m| m.id).max().unwrap_or(0) + 1;
+        self.messages.push(QueuedEmail {
+            id,
+            account: account.to_string(),
+            email,
+            send_at,
+        });
+        id
+    }
+
+    /// Replace a queued message after it was edited, keeping its place in
+    /// the queue if it is still there
+    pub fn update(&mut self, id: u64, email: OutgoingEmail, send_at: DateTime<Utc>) -> bool {
+        match self.messages.iter_mut().find(|m| m.id == id) {
+            Some(queued) => {
+                queued.email = email;
+                queued.send_at = send_at;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn reschedule(&mut self, id: u64, send_at: DateTime<Utc>) -> bool {
+        match self.messages.iter_mut().find(|m| m.id == id) {
+            Some(queued) => {
+                queued.send_at = send_at;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_due(&self) -> Vec<QueuedEmail> {
+        let now = Utc::now();
+        self.messages.iter().filter(|m| m.send_at <= now).cloned().collect()
+    }
+
+    pub fn remove(&mut self, id: u64) -> Option<QueuedEmail> {
+        let pos = self.messages.iter().position(|m| m.id == id)?;
+        Some(self.messages.remove(pos))
+    }
+}
@@ -1,12 +1,46 @@
-use anyhow::Result;
-use chrono::{DateTime, Duration, Utc};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveTime, Utc, Weekday};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 
+use crate::config::SnoozeConfig;
+use crate::email::{Email, OutgoingEmail};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReminderKind {
+    /// An archived message, back in the inbox when due
+    #[default]
+    Snooze,
+    /// A sent message, back in the inbox when due unless someone replied
+    FollowUp,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Reminder {
-    pub uid: u32,
+    /// Names the reminder within the file, to cancel or reschedule it
+    #[serde(default)]
+    pub id: u64,
+    #[serde(default)]
+    pub kind: ReminderKind,
+    /// Address of the account the message belongs to
+    #[serde(default)]
+    pub account: String,
+    /// How the message is found again once it is due. UIDs change as it
+    /// moves between folders, these do not.
+    #[serde(default)]
+    pub message_id: Option<String>,
+    /// Gmail's X-GM-MSGID
+    #[serde(default)]
+    pub gmail_id: Option<u64>,
+    #[serde(default)]
+    pub subject: String,
+    #[serde(default)]
+    pub from: String,
+    /// Recipients of a follow-up
+    #[serde(default)]
+    pub to: String,
     pub return_time: DateTime<Utc>,
 }
 
@@ -26,12 +60,23 @@ impl RemindersFile {
 
     pub fn load() -> Result<Self> {
         let path = Self::path()?;
-        if path.exists() {
+        let mut file: Self = if path.exists() {
             let content = fs::read_to_string(&path)?;
-            Ok(serde_json::from_str(&content)?)
+            serde_json::from_str(&content)?
         } else {
-            Ok(Self::default())
+            Self::default()
+        };
+        // Files from before reminders had IDs
+        let next = file.next_id();
+        let unnumbered = file.reminders.iter_mut().filter(|r| r.id == 0);
+        for (id, reminder) in (next..).zip(unnumbered) {
+            reminder.id = id;
         }
+        Ok(file)
+    }
+
+    fn next_id(&self) -> u64 {
+        self.reminders.iter().map(|r| r.id).max().unwrap_or(0) + 1
     }
 
     pub fn save(&self) -> Result<()> {
@@ -41,42 +86,80 @@ impl RemindersFile {
         Ok(())
     }
 
-    pub fn add_reminder(&mut self, uid: u32, return_time: DateTime<Utc>) {
-        self.reminders.push(Reminder { uid, return_time });
+    /// Remind of `email` from the account with address `account`
+    pub fn add_reminder(&mut self, account: &str, email: &Email, return_time: DateTime<Utc>) {
+        let id = self.next_id();
+        self.reminders.push(Reminder {
+            id,
+            kind: ReminderKind::Snooze,
+            account: account.to_string(),
+            message_id: email.message_id.clone(),
+            gmail_id: email.gmail_id,
+            subject: email.subject.clone(),
+            from: email.from.clone(),
+            to: String::new(),
+            return_time,
+        });
+    }
+
+    /// Remind of `email`, sent from `account`, if nobody has replied to it
+    /// by `return_time`
+    pub fn add_follow_up(&mut self, account: &str, email: &OutgoingEmail, return_time: DateTime<Utc>) {
+        let id = self.next_id();
+        self.reminders.push(Reminder {
+            id,
+            kind: ReminderKind::FollowUp,
+            account: account.to_string(),
+            message_id: email.message_id.clone(),
+            gmail_id: None,
+            subject: email.subject.clone(),
+            from: account.to_string(),
+            to: email.to.clone(),
+            return_time,
+        });
     }
 
-    pub fn get_due_reminders(&self) -> Vec<u32> {
+    pub fn get_due_reminders(&self) -> Vec<Reminder> {
         let now = Utc::now();
         self.reminders
             .iter()
             .filter(|r| r.return_time <= now)
-            .map(|r| r.uid)
+            .cloned()
             .collect()
     }
 
-    pub fn remove_reminder(&mut self, uid: u32) {
-        self.reminders.retain(|r| r.uid != uid);
+    /// Remove a reminder, returning it if it was there
+    pub fn remove_reminder(&mut self, id: u64) -> Option<Reminder> {
+        let pos = self.reminders.iter().position(|r| r.id == id)?;
+        Some(self.reminders.remove(pos))
+    }
+
+    pub fn reschedule(&mut self, id: u64, return_time: DateTime<Utc>) -> bool {
+        match self.reminders.iter_mut().find(|r| r.id == id) {
+            Some(reminder) => {
+                reminder.return_time = return_time;
+                true
+            }
+            None => false,
+        }
     }
 }
 
+/// Read a duration like "2 days", "3h" or "90 min"
 pub fn parse_duration(input: &str) -> Result<Duration> {
-    let parts: Vec<&str> = input.trim().split_whitespace().collect();
-    
-    if parts.len() != 2 {
-        return Err(anyhow::anyhow!("Invalid format. Use: '1 hour', '2 days', etc."));
-    }
+    let compact: String = input.split_whitespace().collect();
+    let digits = compact.find(|c: char| !c.is_ascii_digit()).unwrap_or(compact.len());
+    let (count, unit) = compact.split_at(digits);
 
-    let count: i64 = parts[0]
+    let count: i64 = count
         .parse()
-        .map_err(|_| anyhow::anyhow!("Invalid number: {}", parts[0]))?;
-    
-    let unit = parts[1].to_lowercase();
-    
-    let duration = match unit.as_str() {
-        "minute" | "minutes" => Duration::minutes(count),
-        "hour" | "hours" => Duration::hours(count),
-        "day" | "days" => Duration::days(count),
-        "week" | "weeks" => Duration::days(count * 7),
+        .map_err(|_| anyhow::anyhow!("Invalid format. Use: '1 hour', '2 days', etc."))?;
+
+    let duration = match unit.to_lowercase().as_str() {
+        "m" | "min" | "mins" | "minute" | "minutes" => Duration::minutes(count),
+        "h" | "hr" | "hrs" | "hour" | "hours" => Duration::hours(count),
+        "d" | "day" | "days" => Duration::days(count),
+        "w" | "wk" | "wks" | "week" | "weeks" => Duration::days(count * 7),
         "month" | "months" => Duration::days(count * 30),
         _ => return Err(anyhow::anyhow!("Unknown time unit: {}", unit)),
     };
@@ -84,8 +167,177 @@ pub fn parse_duration(input: &str) -> Result<Duration> {
     Ok(duration)
 }
 
-pub fn calculate_return_time(duration_str: &str) -> Result<DateTime<Utc>> {
-    let duration = parse_duration(duration_str)?;
-    let now = Utc::now();
-    Ok(now + duration)
+/// Work out when a reminder typed as `input` is due, counting from `now`.
+///
+/// Takes durations ("2 days", "in 3h"), days ("tomorrow", "monday", "next
+/// week", "this weekend") optionally followed by a time ("tomorrow 9am",
+/// "friday evening"), a time alone ("5pm", "tonight"), or a date
+/// ("2026-11-01 14:00"). Days without a time mean the morning, as set in
+/// `times`.
+pub fn parse_when(input: &str, now: DateTime<Local>, times: &SnoozeConfig) -> Result<DateTime<Local>> {
+    let input = input.trim().to_lowercase();
+    let words: Vec<&str> = input.split_whitespace().collect();
+    if words.is_empty() {
+        bail!("Type when, e.g. tomorrow 9am");
+    }
+
+    let duration_words = words.strip_prefix(&["in"]).unwrap_or(&words);
+    if let Ok(duration) = parse_duration(&duration_words.join(" ")) {
+        if duration <= Duration::zero() {
+            bail!("That is now, type a longer time");
+        }
+        return Ok(now + duration);
+    }
+
+    let today = now.date_naive();
+    let (day, rest) = match words.as_slice() {
+        ["today", rest @ ..] => (Some(today), rest),
+        ["tonight"] => (Some(today), &["tonight"][..]),
+        ["tomorrow", rest @ ..] => (Some(today + Duration::days(1)), rest),
+        ["next", "week", rest @ ..] => {
+            (Some(next_weekday(today, Weekday::Mon)), rest)
+        }
+        ["this" | "next" | "the", "weekend", rest @ ..] | ["weekend", rest @ ..] => {
+            let day = match today.weekday() {
+                Weekday::Sat => today + Duration::days(1),
+                _ => next_weekday(today, Weekday::Sat),
+            };
+            (Some(day), rest)
+        }
+        ["next" | "on" | "this", day, rest @ ..] | [day, rest @ ..] if parse_weekday(day).is_some() => {
+            let weekday = parse_weekday(day).expect("matched above");
+            (Some(next_weekday(today, weekday)), rest)
+        }
+        [date, rest @ ..] if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok() => {
+            (NaiveDate::parse_from_str(date, "%Y-%m-%d").ok(), rest)
+        }
+        _ => (None, &words[..]),
+    };
+
+    let rest = rest.strip_prefix(&["at"]).unwrap_or(rest);
+    let time = if rest.is_empty() {
+        None
+    } else {
+        Some(parse_time(&rest.join(""), times).ok_or_else(|| anyhow::anyhow!("Unknown time: {}", rest.join(" ")))?)
+    };
+
+    let at = match (day, time) {
+        (Some(day), time) => local(day, time.unwrap_or_else(|| times.morning()))?,
+        // A time alone is the next time the clock shows it
+        (None, Some(time)) => {
+            let at = local(today, time)?;
+            if at > now { at } else { local(today + Duration::days(1), time)? }
+        }
+        (None, None) => bail!("Type when, e.g. tomorrow 9am"),
+    };
+
+    if at <= now {
+        bail!("That time has passed");
+    }
+    Ok(at)
+}
+
+/// A time of day: "9am", "9:30pm", "14:00", "noon", or a part of the day
+fn parse_time(text: &str, times: &SnoozeConfig) -> Option<NaiveTime> {
+    match text {
+        "morning" => return Some(times.morning()),
+        "afternoon" => return Some(times.afternoon()),
+        "tonight" | "evening" | "night" => return Some(times.tonight()),
+        "noon" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return NaiveTime::from_hms_opt(0, 0, 0),
+        _ => {}
+    }
+
+    let (clock, pm) = if let Some(clock) = text.strip_suffix("am") {
+        (clock, Some(false))
+    } else if let Some(clock) = text.strip_suffix("pm") {
+        (clock, Some(true))
+    } else {
+        (text, None)
+    };
+    let (hour, minute): (u32, u32) = match clock.split_once(':') {
+        Some((hour, minute)) => (hour.parse().ok()?, minute.parse().ok()?),
+        None => (clock.parse().ok()?, 0),
+    };
+    let hour = match pm {
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some(pm) => hour % 12 + if pm { 12 } else { 0 },
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    let weekday = match word {
+        "mon" | "monday" => Weekday::Mon,
+        "tue" | "tues" | "tuesday" => Weekday::Tue,
+        "wed" | "wednesday" => Weekday::Wed,
+        "thu" | "thurs" | "thursday" => Weekday::Thu,
+        "fri" | "friday" => Weekday::Fri,
+        "sat" | "saturday" => Weekday::Sat,
+        "sun" | "sunday" => Weekday::Sun,
+        _ => return None,
+    };
+    Some(weekday)
+}
+
+/// The first `weekday` after `day`, a week later if `day` is one
+fn next_weekday(day: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let ahead = (weekday.num_days_from_monday() + 7 - day.weekday().num_days_from_monday()) % 7;
+    day + Duration::days(if ahead == 0 { 7 } else { i64::from(ahead) })
+}
+
+fn local(day: NaiveDate, time: NaiveTime) -> Result<DateTime<Local>> {
+    day.and_time(time)
+        .and_local_timezone(Local)
+        .earliest()
+        .ok_or_else(|| anyhow::anyhow!("That time does not exist here"))
+}
+
+pub fn calculate_return_time(input: &str, times: &SnoozeConfig) -> Result<DateTime<Utc>> {
+    Ok(parse_when(input, Local::now(), times)?.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap()
+    }
+
+    /// Monday 2026-10-12, 10:00
+    fn monday() -> DateTime<Local> {
+        at(2026, 10, 12, 10, 0)
+    }
+
+    fn when(input: &str, now: DateTime<Local>) -> Result<DateTime<Local>> {
+        parse_when(input, now, &SnoozeConfig::default())
+    }
+
+    #[test]
+    fn days_with_and_without_a_time() {
+        assert_eq!(when("tomorrow 9am", monday()).unwrap(), at(2026, 10, 13, 9, 0));
+        assert_eq!(when("monday", monday()).unwrap(), at(2026, 10, 19, 8, 0));
+        assert_eq!(when("next week", monday()).unwrap(), at(2026, 10, 19, 8, 0));
+        assert_eq!(when("this weekend", at(2026, 10, 17, 10, 0)).unwrap(), at(2026, 10, 18, 8, 0));
+        assert_eq!(when("2026-11-01 14:00", monday()).unwrap(), at(2026, 11, 1, 14, 0));
+    }
+
+    #[test]
+    fn times_alone() {
+        assert_eq!(when("12pm", monday()).unwrap(), at(2026, 10, 12, 12, 0));
+        assert_eq!(when("12am", monday()).unwrap(), at(2026, 10, 13, 0, 0));
+        assert_eq!(when("tonight", monday()).unwrap(), at(2026, 10, 12, 20, 0));
+        assert!(when("tonight", at(2026, 10, 12, 21, 0)).is_err());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(when("in 3h", monday()).unwrap(), at(2026, 10, 12, 13, 0));
+        assert_eq!(when("2 days", monday()).unwrap(), at(2026, 10, 14, 10, 0));
+        assert!(when("0 days", monday()).is_err());
+        assert!(when("in 0m", monday()).is_err());
+    }
 }
@@ -1,10 +1,14 @@
-use crate::email::Email;
+use std::borrow::Cow;
+use crate::email::{conversations, utf7, Email, FolderInfo, MessageKey, OutgoingEmail, SpecialUse};
+use crate::config::SnoozeConfig;
+use crate::outbox::QueuedEmail;
+use crate::reminders::{self, Reminder, ReminderKind};
 use super::theme::Theme;
 use super::utils::{relative_time, truncate};
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
 use ratatui::{
-    layout::{Constraint, Direction, Layout, Rect, Margin},
+    layout::{Alignment, Constraint, Direction, Layout, Rect, Margin},
     style::{Modifier, Style},
     text::{Line, Span, Text},
     widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap, Padding},
@@ -15,6 +19,7 @@ use ratatui::{
 // Types
 // ============================================================================
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum View {
     Inbox,
@@ -24,45 +29,69 @@ pub enum View {
     Search,
     Command,
     Remind,
+    /// Pending reminders, from `:reminders`
+    Reminders,
+    /// Messages scheduled to be sent later, from `:outbox`
+    Outbox,
+    /// Fuzzy list of labels or folders for the messages at hand
+    Picker,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub enum Folder {
+    #[default]
     Inbox,
     Sent,
     Drafts,
     Trash,
     Archive,
+    Junk,
+    /// Gmail's view of every \Flagged message
+    Starred,
+    /// Any other folder or Gmail label, by its IMAP name
+    Label(String),
+    /// Virtual folder of what a server search found, by its query
+    Search(String),
+    /// Virtual folder merging the inboxes of every account
+    AllInboxes,
 }
 
 impl Folder {
-    pub fn imap_name(&self) -> &'static str {
+    /// What the folder is for, for the ones found by that rather than name
+    pub fn special_use(&self) -> Option<SpecialUse> {
         match self {
-            Folder::Inbox => "INBOX",
-            Folder::Sent => "[Gmail]/Sent Mail",
-            Folder::Drafts => "[Gmail]/Drafts",
-            Folder::Trash => "[Gmail]/Trash",
-            Folder::Archive => "[Gmail]/All Mail",
+            Folder::Sent => Some(SpecialUse::Sent),
+            Folder::Drafts => Some(SpecialUse::Drafts),
+            Folder::Trash => Some(SpecialUse::Trash),
+            Folder::Archive => Some(SpecialUse::Archive),
+            Folder::Junk => Some(SpecialUse::Junk),
+            Folder::Starred => Some(SpecialUse::Flagged),
+            Folder::Inbox | Folder::Label(_) | Folder::Search(_) | Folder::AllInboxes => None,
         }
     }
 
-    pub fn display_name(&self) -> &'static str {
+    /// The IMAP name of a folder that has the same one everywhere
+    fn fixed_name(&self) -> Option<&str> {
         match self {
-            Folder::Inbox => "Inbox",
-            Folder::Sent => "Sent",
-            Folder::Drafts => "Drafts",
-            Folder::Trash => "Trash",
-            Folder::Archive => "Archive",
+            Folder::Inbox | Folder::AllInboxes => Some("INBOX"),
+            Folder::Label(name) => Some(name),
+            // Up to the server, and a search is no folder at all
+            _ => None,
         }
     }
-    
-    pub fn icon(&self) -> &'static str {
+
+    pub fn display_name(&self) -> Cow<'_, str> {
         match self {
-            Folder::Inbox => "󰇰",
-            Folder::Sent => "󰑊",
-            Folder::Drafts => "󰻣",
-            Folder::Trash => "󰆴",
-            Folder::Archive => "󰀼",
+            Folder::Inbox => "Inbox".into(),
+            Folder::Sent => "Sent".into(),
+            Folder::Drafts => "Drafts".into(),
+            Folder::Trash => "Trash".into(),
+            Folder::Archive => "Archive".into(),
+            Folder::Junk => "Junk".into(),
+            Folder::Starred => "Starred".into(),
+            Folder::Label(name) => utf7::decode(name).into(),
+            Folder::Search(query) => format!("Search: {}", query).into(),
+            Folder::AllInboxes => "All Inboxes".into(),
         }
     }
 }
@@ -75,14 +104,6 @@ pub enum ComposeField {
     Body,
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum ComposeMode {
-    New,
-    Reply,
-    ReplyAll,
-    Forward,
-}
-
 #[derive(Debug, Clone, PartialEq)]
 pub enum EditMode {
     Normal,
@@ -97,8 +118,9 @@ pub enum ImportanceFilter {
     NotImportant,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub enum VimOperator {
+    #[default]
     None,
     Delete,  // d
     Change,  // c
@@ -110,28 +132,22 @@ pub struct VimState {
     pub operator: VimOperator,
 }
 
-impl Default for VimOperator {
-    fn default() -> Self {
-        VimOperator::None
-    }
-}
-
 #[derive(Debug, Clone)]
 pub struct EmailInChain {
     pub from: String,
     pub date: Option<chrono::DateTime<chrono::Utc>>,
     pub body: String,
-    pub message_id: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ComposeState {
+    /// Account the message is sent from
+    pub account: usize,
     pub to: String,
     pub cc: String,
     pub subject: String,
     pub body: String,
     pub active_field: ComposeField,
-    pub mode: ComposeMode,
     pub edit_mode: EditMode,
     pub cursor_pos: usize,
     pub reply_chain: Vec<EmailInChain>,
@@ -139,17 +155,49 @@ pub struct ComposeState {
     pub in_reply_to: Option<String>,
     pub references: Vec<String>,
     pub vim: VimState,
+    /// The outbox entry being edited
+    pub queued: Option<u64>,
+}
+
+impl ComposeState {
+    /// Reopen a message that could not be sent or saved
+    pub fn from_outgoing(account: usize, email: OutgoingEmail) -> Self {
+        Self {
+            account,
+            to: email.to,
+            cc: email.cc,
+            subject: email.subject,
+            body: email.body,
+            in_reply_to: email.in_reply_to,
+            references: email.references,
+            active_field: ComposeField::Body,
+            edit_mode: EditMode::Normal,
+            ..Default::default()
+        }
+    }
+
+    pub fn to_outgoing(&self) -> OutgoingEmail {
+        OutgoingEmail {
+            to: self.to.clone(),
+            cc: self.cc.clone(),
+            subject: self.subject.clone(),
+            body: self.body.clone(),
+            in_reply_to: self.in_reply_to.clone(),
+            references: self.references.clone(),
+            message_id: None,
+        }
+    }
 }
 
 impl Default for ComposeState {
     fn default() -> Self {
         Self {
+            account: 0,
             to: String::new(),
             cc: String::new(),
             subject: String::new(),
             body: String::new(),
             active_field: ComposeField::To,
-            mode: ComposeMode::New,
             edit_mode: EditMode::Insert,
             cursor_pos: 0,
             reply_chain: Vec::new(),
@@ -157,6 +205,7 @@ impl Default for ComposeState {
             in_reply_to: None,
             references: Vec::new(),
             vim: VimState::default(),
+            queued: None,
         }
     }
 }
@@ -185,12 +234,24 @@ pub const COMMANDS: &[Command] = &[
     Command { name: "drafts", description: "Go to Drafts" },
     Command { name: "trash", description: "Go to Trash" },
     Command { name: "archive", description: "Go to Archive" },
+    Command { name: "starred", description: "Go to Starred" },
+    Command { name: "junk", description: "Go to Junk" },
+    Command { name: "all", description: "Go to All Inboxes" },
+    Command { name: "search", description: "Search the whole mailbox on the server (:search <query>)" },
+    Command { name: "folder", description: "Go to any folder or label (:folder <name>)" },
+    Command { name: "folders", description: "Toggle the folder sidebar" },
+    Command { name: "account", description: "Switch account (:account <name>)" },
+    Command { name: "move", description: "Move to a folder (:move <folder>)" },
+    Command { name: "label", description: "Add or remove a Gmail label (:label <name>)" },
+    Command { name: "threads", description: "Toggle conversation view" },
+    Command { name: "reminders", description: "Pending reminders" },
+    Command { name: "followup", description: "Remind me if nobody replies to the last sent message" },
+    Command { name: "outbox", description: "Messages scheduled to be sent later" },
 ];
 
 #[derive(Debug, Default)]
 pub struct CommandState {
     pub input: String,
-    pub cursor: usize,
     pub suggestions: Vec<usize>,
     pub selected: usize,
 }
@@ -224,16 +285,187 @@ impl CommandState {
     }
 }
 
+/// A request running on an account's background worker
+#[derive(Debug, Clone)]
+pub struct Task {
+    pub account: usize,
+    pub label: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct Notification {
     pub message: String,
     pub is_error: bool,
 }
 
+/// What the Remind popup sets a time for
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RemindTarget {
+    /// Snooze the selected email
+    #[default]
+    Selected,
+    /// Move an existing reminder
+    Reschedule(u64),
+    /// Follow up on the last sent message
+    FollowUp,
+    /// Send the message being composed later
+    SendLater,
+    /// Move a queued message
+    RescheduleSend(u64),
+}
+
 #[derive(Debug, Default)]
 pub struct RemindState {
     pub input: String,
     pub cursor: usize,
+    pub target: RemindTarget,
+}
+
+/// What choosing in the picker does
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PickerKind {
+    /// Put a Gmail label on, or take it off
+    #[default]
+    Label,
+    /// Move to a folder
+    Move,
+}
+
+/// The label and folder picker
+#[derive(Debug)]
+pub struct PickerState {
+    pub kind: PickerKind,
+    pub query: String,
+    /// Label or folder names, in modified UTF-7
+    pub options: Vec<String>,
+    /// Indices into `options` that match `query`, best first
+    pub matches: Vec<usize>,
+    pub selected: usize,
+    /// Messages the choice applies to
+    pub targets: Vec<MessageKey>,
+    /// Where Esc or a choice goes back to
+    pub from: View,
+}
+
+impl Default for PickerState {
+    fn default() -> Self {
+        Self {
+            kind: PickerKind::Label,
+            query: String::new(),
+            options: Vec::new(),
+            matches: Vec::new(),
+            selected: 0,
+            targets: Vec::new(),
+            from: View::Inbox,
+        }
+    }
+}
+
+impl PickerState {
+    pub fn update_matches(&mut self) {
+        let matcher = SkimMatcherV2::default();
+        let mut scored: Vec<(i64, usize)> = self
+            .options
+            .iter()
+            .enumerate()
+            .filter_map(|(i, option)| {
+                matcher
+                    .fuzzy_match(&utf7::decode(option), &self.query)
+                    .map(|score| (score, i))
+            })
+            .collect();
+        // Stable, so equal scores keep the server's order
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + 1) % self.matches.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = self.selected.checked_sub(1).unwrap_or(self.matches.len() - 1);
+        }
+    }
+
+    /// The highlighted option, or a new label by the name typed when
+    /// nothing matches
+    pub fn choice(&self) -> Option<String> {
+        match self.matches.get(self.selected) {
+            Some(&i) => Some(self.options[i].clone()),
+            None if self.kind == PickerKind::Label && !self.query.trim().is_empty() => {
+                Some(utf7::encode(self.query.trim()))
+            }
+            None => None,
+        }
+    }
+}
+
+/// Whether a folder is one of the user's Gmail labels, rather than INBOX
+/// or one under Gmail's own `[Gmail]` (`[Google Mail]`) root
+fn is_user_label(name: &str) -> bool {
+    !name.eq_ignore_ascii_case("INBOX") && !name.starts_with("[Gmail]/") && !name.starts_with("[Google Mail]/")
+}
+
+/// The labels worth showing of a message: the user's own but the folder
+/// on screen, readable
+fn label_chips(labels: &[String], folder: &str) -> Vec<String> {
+    labels
+        .iter()
+        .filter(|l| !l.starts_with('\\') && *l != folder)
+        .map(|l| utf7::decode(l))
+        .collect()
+}
+
+/// An operation `u` can take back
+#[derive(Debug)]
+pub enum JournalEntry {
+    /// Messages taken out of their folders by `verb`, "archive", "delete"
+    /// or "move"
+    Removed { verb: &'static str, emails: Vec<Email> },
+    /// A star put on or taken off
+    Starred(MessageKey),
+}
+
+/// How many operations can be undone in a row
+const JOURNAL_LEN: usize = 50;
+
+/// Columns taken by the folder sidebar, borders included
+const SIDEBAR_WIDTH: u16 = 28;
+
+/// A message held back after Ctrl+s, so sending can still be undone
+pub struct PendingSend {
+    pub compose: ComposeState,
+    pub send_at: std::time::Instant,
+}
+
+/// What the outbox view shows, a copy of the outbox file
+#[derive(Debug, Default)]
+pub struct OutboxList {
+    /// Soonest first
+    pub items: Vec<QueuedEmail>,
+    pub selected: usize,
+}
+
+/// The folder list left of the inbox
+#[derive(Debug, Default)]
+pub struct Sidebar {
+    pub visible: bool,
+    /// Keys move through the folders instead of the messages
+    pub focused: bool,
+    pub selected: usize,
+}
+
+/// What the reminders view shows, a copy of the reminders file
+#[derive(Debug, Default)]
+pub struct ReminderList {
+    /// Soonest first
+    pub items: Vec<Reminder>,
+    pub selected: usize,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
@@ -250,53 +482,181 @@ pub struct SelectionState {
     pub anchor: Option<usize>, // Index where selection started
 }
 
+/// Fetching of older mail past the end of the list
+#[derive(Debug, Default)]
+pub struct Paging {
+    /// Page requests still out, one per account in All Inboxes
+    pub pending: usize,
+    /// Rows the outstanding round has added so far
+    added: usize,
+    /// The last round added nothing, the folder has no older mail
+    pub exhausted: bool,
+}
+
+/// The conversation open in the email view in thread mode
+#[derive(Debug, Default)]
+pub struct Conversation {
+    /// Row the state belongs to, it starts over when another one is opened
+    row: Option<MessageKey>,
+    /// Position of the focused message, oldest first
+    focus: usize,
+    expanded: std::collections::HashSet<MessageKey>,
+    /// Scroll to the focused message on the next render
+    scroll_to_focus: bool,
+}
+
+/// Mailbox state of an account that is not currently shown
+pub struct MailboxState {
+    emails: Vec<Email>,
+    list_state: ListState,
+    current_folder: Folder,
+    search: SearchState,
+    selected: std::collections::HashSet<MessageKey>,
+    selection_state: SelectionState,
+    importance_filter: ImportanceFilter,
+}
+
+impl Default for MailboxState {
+    fn default() -> Self {
+        let mut list_state = ListState::default();
+        list_state.select(Some(0));
+
+        Self {
+            emails: Vec::new(),
+            list_state,
+            current_folder: Folder::Inbox,
+            search: SearchState::default(),
+            selected: std::collections::HashSet::new(),
+            selection_state: SelectionState::default(),
+            importance_filter: ImportanceFilter::default(),
+        }
+    }
+}
+
+pub struct AccountTab {
+    pub name: String,
+    parked: Option<MailboxState>,
+    /// From LIST, empty until the worker reports
+    pub folders: Vec<FolderInfo>,
+    /// Whether the server is Gmail's, None until the worker reports
+    pub gmail: Option<bool>,
+}
+
+/// Insert emails the list does not have yet at the top, keeping the
+/// highlighted row on the same message
+fn prepend_emails(emails: &mut Vec<Email>, list_state: &mut ListState, new: Vec<Email>) -> usize {
+    let fresh: Vec<Email> = new
+        .into_iter()
+        .filter(|n| !emails.iter().any(|e| e.key() == n.key()))
+        .collect();
+    let count = fresh.len();
+    if count > 0 {
+        emails.splice(0..0, fresh);
+        if let Some(idx) = list_state.selected() {
+            list_state.select(Some(idx + count));
+        }
+    }
+    count
+}
+
 // ============================================================================
 // App State
 // ============================================================================
 
 pub struct App {
+    pub accounts: Vec<AccountTab>,
+    pub active_account: usize,
     pub emails: Vec<Email>,
     pub list_state: ListState,
     pub view: View,
     pub scroll_offset: u16,
     pub compose: ComposeState,
     pub notification: Option<Notification>,
+    pub tasks: Vec<Task>,
     pub should_quit: bool,
     pub pending_command: Option<char>,
     pub current_folder: Folder,
     pub search: SearchState,
     pub command: CommandState,
     pub remind: RemindState,
+    pub picker: PickerState,
     pub theme: Theme,
-    pub starred: std::collections::HashSet<u32>,
-    pub selected: std::collections::HashSet<u32>,
+    pub selected: std::collections::HashSet<MessageKey>,
     pub selection_state: SelectionState,
     pub importance_filter: ImportanceFilter,
+    pub paging: Paging,
+    /// Messages whose body has been asked for and not arrived yet
+    pub body_requests: std::collections::HashSet<MessageKey>,
+    /// One row per conversation instead of per message
+    pub threaded: bool,
+    pub conversation: Conversation,
+    pub reminders: ReminderList,
+    /// Reminders whose message is being moved back to the inbox
+    pub resurfacing: std::collections::HashSet<u64>,
+    /// For the Remind popup's preview
+    pub snooze: SnoozeConfig,
+    /// The last message sent and its account, to follow up on
+    pub last_sent: Option<(usize, OutgoingEmail)>,
+    pub outbox: OutboxList,
+    pub sidebar: Sidebar,
+    /// Queued messages handed to SMTP and not confirmed yet
+    pub sending: std::collections::HashSet<u64>,
+    pub pending_send: Option<PendingSend>,
+    /// Undoable operations, the latest last
+    pub journal: Vec<JournalEntry>,
+    /// Messages taken off the list while the server archives or deletes
+    /// them, to put back if it cannot, with the folder they were listed in
+    pub removing: std::collections::HashMap<MessageKey, (Folder, Email)>,
 }
 
 impl App {
-    pub fn new() -> Self {
+    pub fn new(account_names: Vec<String>) -> Self {
         let mut list_state = ListState::default();
         list_state.select(Some(0));
 
         Self {
+            accounts: account_names
+                .into_iter()
+                .map(|name| AccountTab {
+                    name,
+                    parked: None,
+                    folders: Vec::new(),
+                    gmail: None,
+                })
+                .collect(),
+            active_account: 0,
             emails: Vec::new(),
             list_state,
             view: View::Inbox,
             scroll_offset: 0,
             compose: ComposeState::default(),
             notification: None,
+            tasks: Vec::new(),
             should_quit: false,
             pending_command: None,
             current_folder: Folder::Inbox,
             search: SearchState::default(),
             command: CommandState::default(),
             remind: RemindState::default(),
+            picker: PickerState::default(),
             theme: Theme::default(),
-            starred: std::collections::HashSet::new(),
             selected: std::collections::HashSet::new(),
             selection_state: SelectionState::default(),
             importance_filter: ImportanceFilter::default(),
+            paging: Paging::default(),
+            body_requests: std::collections::HashSet::new(),
+            threaded: false,
+            conversation: Conversation::default(),
+            reminders: ReminderList::default(),
+            resurfacing: std::collections::HashSet::new(),
+            snooze: SnoozeConfig::default(),
+            last_sent: None,
+            outbox: OutboxList::default(),
+            sidebar: Sidebar::default(),
+            sending: std::collections::HashSet::new(),
+            pending_send: None,
+            journal: Vec::new(),
+            removing: std::collections::HashMap::new(),
         }
     }
 
@@ -318,6 +678,67 @@ impl App {
         self.notification = None;
     }
 
+    pub fn begin_task(&mut self, account: usize, label: &str) {
+        self.tasks.push(Task {
+            account,
+            label: label.to_string(),
+        });
+    }
+
+    /// Workers answer in order, so the oldest task of the account is done
+    pub fn finish_task(&mut self, account: usize) {
+        if let Some(pos) = self.tasks.iter().position(|t| t.account == account) {
+            self.tasks.remove(pos);
+        }
+    }
+
+    pub fn active_account_name(&self) -> &str {
+        self.accounts
+            .get(self.active_account)
+            .map(|a| a.name.as_str())
+            .unwrap_or("")
+    }
+
+    pub fn account_index(&self, name: &str) -> Option<usize> {
+        self.accounts
+            .iter()
+            .position(|a| a.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Park the current account's mailbox and restore the one for `idx`.
+    /// Returns true when the account is shown for the first time and still
+    /// needs its inbox loaded.
+    pub fn switch_account(&mut self, idx: usize) -> bool {
+        if idx >= self.accounts.len() || idx == self.active_account {
+            return false;
+        }
+
+        let parked = MailboxState {
+            emails: std::mem::take(&mut self.emails),
+            list_state: std::mem::take(&mut self.list_state),
+            current_folder: std::mem::take(&mut self.current_folder),
+            search: std::mem::take(&mut self.search),
+            selected: std::mem::take(&mut self.selected),
+            selection_state: std::mem::take(&mut self.selection_state),
+            importance_filter: std::mem::take(&mut self.importance_filter),
+        };
+        self.accounts[self.active_account].parked = Some(parked);
+        self.active_account = idx;
+
+        let first_visit = self.accounts[idx].parked.is_none();
+        let state = self.accounts[idx].parked.take().unwrap_or_default();
+        self.emails = state.emails;
+        self.list_state = state.list_state;
+        self.current_folder = state.current_folder;
+        self.search = state.search;
+        self.selected = state.selected;
+        self.selection_state = state.selection_state;
+        self.importance_filter = state.importance_filter;
+        self.paging = Paging::default();
+        self.scroll_offset = 0;
+        first_visit
+    }
+
     pub fn cycle_importance_filter(&mut self) {
         self.importance_filter = match self.importance_filter {
             ImportanceFilter::All => ImportanceFilter::Important,
@@ -333,35 +754,21 @@ impl App {
         self.notify(&format!("Filter: {}", filter_name));
     }
 
-    pub fn filtered_emails(&self) -> Vec<&Email> {
-        self.emails
-            .iter()
-            .filter(|e| match self.importance_filter {
-                ImportanceFilter::All => true,
-                ImportanceFilter::Important => e.important,
-                ImportanceFilter::NotImportant => !e.important,
-            })
-            .collect()
-    }
-
-    pub fn toggle_star(&mut self) {
-        if let Some(email) = self.selected_email() {
-            let uid = email.uid;
-            if self.starred.contains(&uid) {
-                self.starred.remove(&uid);
-            } else {
-                self.starred.insert(uid);
-            }
+    /// Remember an operation so `u` can take it back
+    pub fn record(&mut self, entry: JournalEntry) {
+        if self.journal.len() == JOURNAL_LEN {
+            self.journal.remove(0);
         }
+        self.journal.push(entry);
     }
 
     pub fn toggle_selection(&mut self) {
         if let Some(email) = self.selected_email() {
-            let uid = email.uid;
-            if self.selected.contains(&uid) {
-                self.selected.remove(&uid);
+            let key = email.key();
+            if self.selected.contains(&key) {
+                self.selected.remove(&key);
             } else {
-                self.selected.insert(uid);
+                self.selected.insert(key);
             }
         }
     }
@@ -385,7 +792,7 @@ impl App {
         if self.selection_state.direction == SelectionDirection::Up {
             if let Some(prev) = prev_selected {
                 if let Some(email) = self.emails.get(prev) {
-                    self.selected.remove(&email.uid);
+                    self.selected.remove(&email.key());
                 }
             }
         } else {
@@ -393,7 +800,7 @@ impl App {
             self.selection_state.direction = SelectionDirection::Down;
             if let Some(curr) = curr_selected {
                 if let Some(email) = self.emails.get(curr) {
-                    self.selected.insert(email.uid);
+                    self.selected.insert(email.key());
                 }
             }
         }
@@ -413,7 +820,7 @@ impl App {
         if self.selection_state.direction == SelectionDirection::Down {
             if let Some(prev) = prev_selected {
                 if let Some(email) = self.emails.get(prev) {
-                    self.selected.remove(&email.uid);
+                    self.selected.remove(&email.key());
                 }
             }
         } else {
@@ -421,46 +828,135 @@ impl App {
             self.selection_state.direction = SelectionDirection::Up;
             if let Some(curr) = curr_selected {
                 if let Some(email) = self.emails.get(curr) {
-                    self.selected.insert(email.uid);
+                    self.selected.insert(email.key());
                 }
             }
         }
     }
 
-    pub fn get_selected_uids(&self) -> Vec<u32> {
-        self.selected.iter().copied().collect()
+    pub fn get_selected_keys(&self) -> Vec<MessageKey> {
+        self.selected.iter().cloned().collect()
     }
 
-    pub fn start_reply(&mut self, reply_all: bool) {
-        if let Some(email) = self.selected_email().cloned() {
-            let mut compose = ComposeState::default();
-            compose.to = email.from_address.clone();
-            compose.subject = if email.subject.starts_with("Re:") {
-                email.subject.clone()
-            } else {
-                format!("Re: {}", email.subject)
-            };
-            compose.mode = if reply_all { ComposeMode::ReplyAll } else { ComposeMode::Reply };
-            
-            compose.in_reply_to = email.message_id.clone();
+    /// The multi-selection if there is one, otherwise the highlighted email.
+    /// In thread mode a row stands for its whole conversation.
+    pub fn target_keys(&self) -> Vec<MessageKey> {
+        let keys: Vec<MessageKey> = if self.selected.is_empty() {
+            self.selected_email().map(|e| e.key()).into_iter().collect()
+        } else {
+            self.get_selected_keys()
+        };
+        if !self.threaded {
+            return keys;
+        }
+        let roots = conversations(&self.emails);
+        let rows: std::collections::HashSet<usize> = keys
+            .iter()
+            .filter_map(|key| self.emails.iter().position(|e| e.key() == *key))
+            .map(|i| roots[i])
+            .collect();
+        self.emails
+            .iter()
+            .zip(&roots)
+            .filter(|(_, root)| rows.contains(root))
+            .map(|(e, _)| e.key())
+            .collect()
+    }
+
+    pub fn find_email(&self, key: &MessageKey) -> Option<&Email> {
+        self.emails.iter().find(|e| e.key() == *key)
+    }
+
+    /// Take emails off the list while the server works on them, keeping
+    /// the cursor in range. `settle_removal` has the last word.
+    pub fn remove_emails(&mut self, keys: &[MessageKey]) {
+        let (removed, kept) = std::mem::take(&mut self.emails)
+            .into_iter()
+            .partition(|e| keys.contains(&e.key()));
+        self.emails = kept;
+        for email in removed {
+            self.removing.insert(email.key(), (self.current_folder.clone(), email));
+        }
+        if self.list_state.selected().unwrap_or(0) >= self.emails.len() && !self.emails.is_empty() {
+            self.list_state.select(Some(self.emails.len() - 1));
+        }
+    }
+
+    /// The server is done with removed emails of `account`: forget those
+    /// in `done`, and put those in `failed` back on the list if the list
+    /// they were taken from, or their folder, is on screen. Returns the
+    /// failed ones.
+    pub fn settle_removal(&mut self, account: usize, done: &[(String, u32)], failed: &[(String, u32)]) -> Vec<Email> {
+        let key = |(folder, uid): &(String, u32)| MessageKey {
+            account,
+            folder: folder.clone(),
+            uid: *uid,
+        };
+        for target in done {
+            self.removing.remove(&key(target));
+        }
+        let failed: Vec<(Folder, Email)> = failed
+            .iter()
+            .filter_map(|target| self.removing.remove(&key(target)))
+            .collect();
+
+        let current = self.selected_email().map(|e| e.key());
+        let back: Vec<Email> = failed
+            .iter()
+            .filter(|(listed, e)| {
+                let on_screen = (account == self.active_account && *listed == self.current_folder)
+                    || self.shows(account, &e.folder);
+                on_screen && self.find_email(&e.key()).is_none()
+            })
+            .map(|(_, e)| e.clone())
+            .collect();
+        if !back.is_empty() {
+            self.emails.extend(back);
+            self.emails.sort_by_key(|e| std::cmp::Reverse(e.date));
+            if let Some(idx) = current.and_then(|key| self.emails.iter().position(|e| e.key() == key)) {
+                self.list_state.select(Some(idx));
+            }
+        }
+        failed.into_iter().map(|(_, e)| e).collect()
+    }
+
+    pub fn start_compose(&mut self) {
+        self.compose = ComposeState {
+            account: self.active_account,
+            ..Default::default()
+        };
+        self.view = View::Compose;
+    }
+
+    pub fn start_reply(&mut self) {
+        if let Some(email) = self.open_email().cloned() {
             let mut refs = email.references.clone();
             if let Some(ref msg_id) = email.message_id {
                 if !refs.contains(msg_id) {
                     refs.push(msg_id.clone());
                 }
             }
-            compose.references = refs;
-            
-            compose.reply_chain = vec![EmailInChain {
-                from: email.from.clone(),
-                date: email.date,
-                body: email.body.clone(),
-                message_id: email.message_id.clone(),
-            }];
-            compose.active_field = ComposeField::Body;
-            compose.edit_mode = EditMode::Insert;
-            compose.cursor_pos = 0;
-            self.compose = compose;
+
+            self.compose = ComposeState {
+                account: email.account,
+                to: email.from_address.clone(),
+                subject: if email.subject.starts_with("Re:") {
+                    email.subject.clone()
+                } else {
+                    format!("Re: {}", email.subject)
+                },
+                in_reply_to: email.message_id.clone(),
+                references: refs,
+                reply_chain: vec![EmailInChain {
+                    from: email.from.clone(),
+                    date: email.date,
+                    body: email.body.clone(),
+                }],
+                active_field: ComposeField::Body,
+                edit_mode: EditMode::Insert,
+                cursor_pos: 0,
+                ..Default::default()
+            };
             self.view = View::Compose;
         }
     }
@@ -472,38 +968,39 @@ impl App {
                 from: e.from,
                 date: e.date,
                 body: e.body,
-                message_id: e.message_id,
             })
             .collect();
     }
 
     pub fn start_forward(&mut self) {
-        if let Some(email) = self.selected_email().cloned() {
-            let mut compose = ComposeState::default();
-            compose.subject = format!("Fwd: {}", email.subject);
-            compose.body = format!(
-                "\n\n---------- Forwarded message ----------\nFrom: {}\nSubject: {}\n\n{}",
-                email.from, email.subject, email.body
-            );
-            compose.mode = ComposeMode::Forward;
-            compose.active_field = ComposeField::To;
-            compose.edit_mode = EditMode::Insert;
-            self.compose = compose;
+        if let Some(email) = self.open_email().cloned() {
+            self.compose = ComposeState {
+                account: email.account,
+                subject: format!("Fwd: {}", email.subject),
+                body: format!(
+                    "\n\n---------- Forwarded message ----------\nFrom: {}\nSubject: {}\n\n{}",
+                    email.from, email.subject, email.body
+                ),
+                active_field: ComposeField::To,
+                edit_mode: EditMode::Insert,
+                ..Default::default()
+            };
             self.view = View::Compose;
         }
     }
 
     pub fn edit_draft(&mut self) {
         if let Some(email) = self.selected_email().cloned() {
-            let mut compose = ComposeState::default();
-            // Extract To and CC from the email (if available in parsed headers)
-            compose.to = email.from_address.clone();
-            compose.subject = email.subject.clone();
-            compose.body = email.body.clone();
-            compose.mode = ComposeMode::New;
-            compose.active_field = ComposeField::To;
-            compose.edit_mode = EditMode::Insert;
-            self.compose = compose;
+            self.compose = ComposeState {
+                account: email.account,
+                // Extract To and CC from the email (if available in parsed headers)
+                to: email.from_address.clone(),
+                subject: email.subject.clone(),
+                body: email.body.clone(),
+                active_field: ComposeField::To,
+                edit_mode: EditMode::Insert,
+                ..Default::default()
+            };
             self.view = View::Compose;
         }
     }
@@ -559,7 +1056,7 @@ impl App {
         let chars: Vec<char> = field.chars().collect();
         let mut pos = self.compose.cursor_pos;
         
-        if pos > 0 { pos -= 1; }
+        pos = pos.saturating_sub(1);
         while pos > 0 && chars[pos].is_whitespace() {
             pos -= 1;
         }
@@ -686,9 +1183,7 @@ impl App {
         while pos < chars.len() && !chars[pos].is_whitespace() {
             pos += 1;
         }
-        if pos > 0 {
-            pos -= 1;
-        }
+        pos = pos.saturating_sub(1);
         self.compose.cursor_pos = pos.min(chars.len().saturating_sub(1));
     }
 
@@ -710,37 +1205,512 @@ impl App {
         }
     }
 
-    // Search
-    pub fn update_search(&mut self) {
-        let matcher = SkimMatcherV2::default();
-        let query = &self.search.query;
-
-        if query.is_empty() {
-            self.search.results = (0..self.emails.len()).collect();
-        } else {
-            self.search.results = self
-                .emails
-                .iter()
-                .enumerate()
-                .filter_map(|(i, email)| {
-                    let text = format!("{} {} {}", email.from, email.subject, email.body);
-                    matcher.fuzzy_match(&text, query).map(|_| i)
-                })
-                .collect();
+    // Search
+    pub fn update_search(&mut self) {
+        let matcher = SkimMatcherV2::default();
+        let query = &self.search.query;
+
+        if query.is_empty() {
+            self.search.results = (0..self.emails.len()).collect();
+        } else {
+            self.search.results = self
+                .emails
+                .iter()
+                .enumerate()
+                .filter_map(|(i, email)| {
+                    let text = format!("{} {} {}", email.from, email.subject, email.body);
+                    matcher.fuzzy_match(&text, query).map(|_| i)
+                })
+                .collect();
+        }
+        self.search.selected = 0;
+    }
+
+    // Email list management
+    pub fn set_emails(&mut self, emails: Vec<Email>) {
+        self.emails = emails;
+        self.paging = Paging::default();
+        if !self.emails.is_empty() {
+            self.list_state.select(Some(0));
+        }
+    }
+
+    /// Take a folder listing from a worker if it belongs to what is on screen.
+    /// Returns false when the listing is stale.
+    pub fn accept_emails(&mut self, account: usize, folder: &str, emails: Vec<Email>) -> bool {
+        if !self.shows(account, folder) {
+            return false;
+        }
+        self.replace_listing(account, emails);
+        true
+    }
+
+    /// Put the listing of `account` on screen in place of the old one
+    fn replace_listing(&mut self, account: usize, emails: Vec<Email>) {
+        let current = self.selected_email().map(|e| e.key());
+        if self.current_folder == Folder::AllInboxes {
+            self.emails.retain(|e| e.account != account);
+            self.emails.extend(emails);
+            self.emails.sort_by_key(|e| std::cmp::Reverse(e.date));
+        } else {
+            self.emails = emails;
+        }
+        self.paging = Paging::default();
+
+        // The cached listing is replaced by the server's, keep the same row
+        let idx = current
+            .and_then(|key| self.emails.iter().position(|e| e.key() == key))
+            .unwrap_or(0);
+        self.list_state.select(Some(idx));
+        if self.search.active {
+            self.update_search();
+        }
+    }
+
+    /// Take an older page from a worker and add it below the list
+    pub fn append_emails(&mut self, account: usize, folder: &str, emails: Vec<Email>) {
+        let emails = self.shows(account, folder).then_some(emails);
+        self.append_page(emails);
+    }
+
+    /// Count an answered page request, adding its emails unless the list
+    /// they were for is gone
+    fn append_page(&mut self, emails: Option<Vec<Email>>) {
+        self.paging.pending = self.paging.pending.saturating_sub(1);
+        if let Some(emails) = emails {
+            let current = self.selected_email().map(|e| e.key());
+            let before = self.emails.len();
+            for email in emails {
+                if !self.emails.iter().any(|e| e.key() == email.key()) {
+                    self.emails.push(email);
+                }
+            }
+            self.paging.added += self.emails.len() - before;
+            if self.current_folder == Folder::AllInboxes {
+                self.emails.sort_by_key(|e| std::cmp::Reverse(e.date));
+                if let Some(idx) = current.and_then(|key| self.emails.iter().position(|e| e.key() == key)) {
+                    self.list_state.select(Some(idx));
+                }
+            }
+        }
+        if self.paging.pending == 0 {
+            self.paging.exhausted = self.paging.added == 0;
+            self.paging.added = 0;
+        }
+    }
+
+    /// Whether a listing of `folder` from `account` belongs on screen
+    fn shows(&self, account: usize, folder: &str) -> bool {
+        match &self.current_folder {
+            Folder::AllInboxes => folder == "INBOX",
+            // Filled by `accept_found` only
+            Folder::Search(_) => false,
+            current => account == self.active_account && self.folder_name(account, current).as_deref() == Some(folder),
+        }
+    }
+
+    /// Take a page of search results from a worker, if that search is
+    /// still on screen
+    pub fn accept_found(&mut self, account: usize, query: &str, start: u32, emails: Vec<Email>) {
+        let shown = account == self.active_account
+            && matches!(&self.current_folder, Folder::Search(q) if q == query);
+        self.accept_page(account, shown, start, emails);
+    }
+
+    /// Take a page of the flagged messages standing in for Starred, if
+    /// Starred is still on screen
+    pub fn accept_flagged(&mut self, account: usize, start: u32, emails: Vec<Email>) {
+        let shown = account == self.active_account && self.current_folder == Folder::Starred;
+        self.accept_page(account, shown, start, emails);
+    }
+
+    fn accept_page(&mut self, account: usize, shown: bool, start: u32, emails: Vec<Email>) {
+        if start > 0 {
+            self.append_page(shown.then_some(emails));
+        } else if shown {
+            self.replace_listing(account, emails);
+        }
+    }
+
+    /// Put mail pushed by the watcher at the top of the list it belongs to,
+    /// keeping the highlighted message. Returns how many landed on screen.
+    pub fn insert_new_emails(&mut self, account: usize, folder: &str, emails: Vec<Email>) -> usize {
+        if self.shows(account, folder) {
+            let count = prepend_emails(&mut self.emails, &mut self.list_state, emails);
+            if let Some(anchor) = self.selection_state.anchor.as_mut() {
+                *anchor += count;
+            }
+            // Search results index into `emails`; new mail is left out of them
+            for idx in &mut self.search.results {
+                *idx += count;
+            }
+            count
+        } else {
+            let parked_folder = self
+                .accounts
+                .get(account)
+                .and_then(|a| a.parked.as_ref())
+                .and_then(|parked| self.folder_name(account, &parked.current_folder));
+            if let Some(parked) = self.accounts.get_mut(account).and_then(|a| a.parked.as_mut()) {
+                if parked_folder.as_deref() == Some(folder) {
+                    prepend_emails(&mut parked.emails, &mut parked.list_state, emails);
+                }
+            }
+            0
+        }
+    }
+
+    /// Fill in a body that was downloaded on demand
+    pub fn set_body(&mut self, key: MessageKey, body: String) {
+        self.body_requests.remove(&key);
+        if let Some(email) = self.emails.iter_mut().find(|e| e.key() == key) {
+            email.body = body;
+            email.body_loaded = true;
+        }
+    }
+
+    /// The selected email and the ones below it in the visible list. In
+    /// thread mode the rest of the selected conversation comes first.
+    pub fn upcoming_emails(&self, count: usize) -> Vec<&Email> {
+        let indices = self.get_visible_indices();
+        let current = self.list_state.selected().unwrap_or(0);
+        let pos = indices.iter().position(|&i| i == current).unwrap_or(0);
+        let mut upcoming = self.selected_thread();
+        upcoming.reverse();
+        upcoming.extend(
+            indices
+                .iter()
+                .skip(pos + 1)
+                .take(count.saturating_sub(1))
+                .filter_map(|&i| self.emails.get(i)),
+        );
+        upcoming
+    }
+
+    pub fn selected_email(&self) -> Option<&Email> {
+        self.list_state.selected().and_then(|i| self.emails.get(i))
+    }
+
+    /// Messages of the selected row's conversation, oldest first. Just the
+    /// selected email outside thread mode.
+    pub fn selected_thread(&self) -> Vec<&Email> {
+        let Some(current) = self.list_state.selected().filter(|&i| i < self.emails.len()) else {
+            return vec![];
+        };
+        if !self.threaded {
+            return vec![&self.emails[current]];
+        }
+        let roots = conversations(&self.emails);
+        let mut thread: Vec<&Email> = self
+            .emails
+            .iter()
+            .zip(&roots)
+            .filter(|(_, &root)| root == roots[current])
+            .map(|(e, _)| e)
+            .collect();
+        // Stable, so messages without a date keep the list's order
+        thread.reverse();
+        thread.sort_by_key(|e| e.date);
+        thread
+    }
+
+    /// The message the email view is about: the focused one of an open
+    /// conversation, otherwise the selected email
+    pub fn open_email(&self) -> Option<&Email> {
+        let thread = self.selected_thread();
+        let focus = self.conversation_focus(thread.len());
+        thread.get(focus).copied()
+    }
+
+    /// Position of the focused message in a conversation of `len`; the
+    /// newest until it is moved
+    fn conversation_focus(&self, len: usize) -> usize {
+        let last = len.saturating_sub(1);
+        let row = self.selected_email().map(|e| e.key());
+        if row.is_some() && self.conversation.row == row {
+            self.conversation.focus.min(last)
+        } else {
+            last
+        }
+    }
+
+    /// Show `items` in the reminders view, keeping the selection in range
+    pub fn set_reminders(&mut self, mut items: Vec<Reminder>) {
+        items.sort_by_key(|r| r.return_time);
+        self.reminders.selected = self.reminders.selected.min(items.len().saturating_sub(1));
+        self.reminders.items = items;
+    }
+
+    pub fn selected_reminder(&self) -> Option<&Reminder> {
+        self.reminders.items.get(self.reminders.selected)
+    }
+
+    /// Take back the message waiting to be sent and reopen it in compose
+    pub fn undo_send(&mut self) {
+        if let Some(pending) = self.pending_send.take() {
+            self.compose = pending.compose;
+            self.view = View::Compose;
+            self.notify("Sending undone");
+        }
+    }
+
+    pub fn set_outbox(&mut self, mut items: Vec<QueuedEmail>) {
+        items.sort_by_key(|m| m.send_at);
+        self.outbox.selected = self.outbox.selected.min(items.len().saturating_sub(1));
+        self.outbox.items = items;
+    }
+
+    pub fn selected_queued(&self) -> Option<&QueuedEmail> {
+        self.outbox.items.get(self.outbox.selected)
+    }
+
+    /// The IMAP name of `folder` on `account`: the folder LIST reported for
+    /// its special use, if there is one. None for a special-use folder
+    /// while LIST has not answered, the worker finds those itself.
+    pub fn folder_name(&self, account: usize, folder: &Folder) -> Option<String> {
+        let Some(use_) = folder.special_use() else {
+            return folder.fixed_name().map(str::to_string);
+        };
+        self.accounts
+            .get(account)?
+            .folders
+            .iter()
+            .find(|f| f.special == Some(use_))
+            .map(|f| f.name.clone())
+    }
+
+    /// The folder the active account's LIST calls `name`, as one of the
+    /// fixed ones if it is
+    pub fn folder_for(&self, name: &str) -> Folder {
+        if name.eq_ignore_ascii_case("INBOX") {
+            return Folder::Inbox;
+        }
+        let special = self
+            .sidebar_folders()
+            .iter()
+            .find(|f| f.name == name)
+            .and_then(|f| f.special);
+        let known = [
+            Folder::Sent,
+            Folder::Drafts,
+            Folder::Trash,
+            Folder::Archive,
+            Folder::Junk,
+            Folder::Starred,
+        ];
+        known
+            .into_iter()
+            .find(|f| special.is_some() && f.special_use() == special)
+            .unwrap_or_else(|| Folder::Label(name.to_string()))
+    }
+
+    /// Open the label picker on the messages at hand, listing the user
+    /// labels LIST reported for the active account
+    pub fn start_label_picker(&mut self) {
+        let targets = self.target_keys();
+        if targets.is_empty() {
+            return;
+        }
+        let not_gmail = targets
+            .iter()
+            .any(|key| self.accounts.get(key.account).and_then(|a| a.gmail) == Some(false));
+        if not_gmail {
+            self.notify_error("Labels are Gmail's, this server only has folders (m to move)");
+            return;
+        }
+        let mut options: Vec<String> = self
+            .sidebar_folders()
+            .iter()
+            .filter(|f| f.special.is_none() && is_user_label(&f.name))
+            .map(|f| f.name.clone())
+            .collect();
+        // Labels of the messages themselves, in case LIST has not answered
+        for key in &targets {
+            if let Some(email) = self.find_email(key) {
+                for label in &email.labels {
+                    if !label.starts_with('\\') && !options.contains(label) {
+                        options.push(label.clone());
+                    }
+                }
+            }
+        }
+        self.picker = PickerState {
+            options,
+            targets,
+            from: self.view.clone(),
+            ..Default::default()
+        };
+        self.picker.update_matches();
+        self.view = View::Picker;
+    }
+
+    /// Open the folder picker on the messages at hand, which must all be
+    /// of the active account as the folders are its own
+    pub fn start_move_picker(&mut self) {
+        let targets = self.target_keys();
+        if targets.is_empty() {
+            return;
+        }
+        if targets.iter().any(|key| key.account != self.active_account) {
+            self.notify_error("Can only move messages of the active account, switch with g1–g9");
+            return;
+        }
+        let current = self.folder_name(self.active_account, &self.current_folder);
+        let options: Vec<String> = self
+            .sidebar_folders()
+            .iter()
+            .filter(|f| Some(&f.name) != current.as_ref())
+            .map(|f| f.name.clone())
+            .collect();
+        if options.is_empty() {
+            self.notify_error("No folders to move to yet");
+            return;
+        }
+        self.picker = PickerState {
+            kind: PickerKind::Move,
+            options,
+            targets,
+            from: self.view.clone(),
+            ..Default::default()
+        };
+        self.picker.update_matches();
+        self.view = View::Picker;
+    }
+
+    /// How many of `keys` carry `label`
+    pub fn label_count(&self, keys: &[MessageKey], label: &str) -> usize {
+        keys.iter()
+            .filter_map(|key| self.find_email(key))
+            .filter(|email| email.labels.iter().any(|l| l == label))
+            .count()
+    }
+
+    /// Put `label` on `keys` or take it off, on screen only
+    pub fn set_label(&mut self, keys: &[MessageKey], label: &str, add: bool) {
+        for email in self.emails.iter_mut().filter(|e| keys.contains(&e.key())) {
+            email.labels.retain(|l| l != label);
+            if add {
+                email.labels.push(label.to_string());
+            }
+        }
+    }
+
+    /// Whether moving `key` to `to` just labels it. Gmail keeps every
+    /// message in All Mail, so out of there only Trash and Spam take it.
+    pub fn move_only_labels(&self, key: &MessageKey, to: &str) -> bool {
+        let Some(account) = self.accounts.get(key.account) else {
+            return false;
+        };
+        let special = |name: &str| account.folders.iter().find(|f| f.name == name).and_then(|f| f.special);
+        special(&key.folder) == Some(SpecialUse::All)
+            && !matches!(special(to), Some(SpecialUse::Trash | SpecialUse::Junk))
+    }
+
+    /// The folders of the active account, as LIST last reported them
+    pub fn sidebar_folders(&self) -> &[FolderInfo] {
+        self.accounts
+            .get(self.active_account)
+            .map(|a| a.folders.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Show the sidebar with the current folder under the cursor, or hide it
+    pub fn toggle_sidebar(&mut self) {
+        self.sidebar.visible = !self.sidebar.visible;
+        self.sidebar.focused = self.sidebar.visible;
+        if self.sidebar.visible {
+            let current = self.folder_name(self.active_account, &self.current_folder);
+            self.sidebar.selected = self
+                .sidebar_folders()
+                .iter()
+                .position(|f| Some(&f.name) == current.as_ref())
+                .unwrap_or(0);
+        }
+    }
+
+    pub fn sidebar_next(&mut self) {
+        let len = self.sidebar_folders().len();
+        if self.sidebar.selected + 1 < len {
+            self.sidebar.selected += 1;
+        }
+    }
+
+    pub fn sidebar_previous(&mut self) {
+        self.sidebar.selected = self.sidebar.selected.saturating_sub(1);
+    }
+
+    /// The folder under the sidebar's cursor
+    pub fn sidebar_folder(&self) -> Option<Folder> {
+        self.sidebar_folders()
+            .get(self.sidebar.selected)
+            .map(|f| self.folder_for(&f.name))
+    }
+
+    pub fn toggle_threads(&mut self) {
+        self.threaded = !self.threaded;
+        self.conversation = Conversation::default();
+        // The selected message may be hidden behind its conversation's row
+        if self.threaded {
+            if let Some(current) = self.list_state.selected().filter(|&i| i < self.emails.len()) {
+                self.list_state.select(Some(conversations(&self.emails)[current]));
+            }
+        }
+        self.notify(if self.threaded { "Conversations" } else { "Messages" });
+    }
+
+    /// Start over with the unread and the newest message expanded when
+    /// another conversation is opened
+    fn sync_conversation(&mut self) {
+        let row = self.selected_email().map(|e| e.key());
+        if self.conversation.row == row {
+            return;
         }
-        self.search.selected = 0;
+        let thread = self.selected_thread();
+        let expanded = thread
+            .iter()
+            .enumerate()
+            .filter(|(i, e)| !e.seen || *i + 1 == thread.len())
+            .map(|(_, e)| e.key())
+            .collect();
+        let focus = thread.len().saturating_sub(1);
+        self.conversation = Conversation {
+            row,
+            focus,
+            expanded,
+            scroll_to_focus: true,
+        };
     }
 
-    // Email list management
-    pub fn set_emails(&mut self, emails: Vec<Email>) {
-        self.emails = emails;
-        if !self.emails.is_empty() {
-            self.list_state.select(Some(0));
+    /// Move the focus `delta` messages through the open conversation
+    pub fn focus_message(&mut self, delta: isize) {
+        self.sync_conversation();
+        let len = self.selected_thread().len();
+        let focus = self.conversation.focus.saturating_add_signed(delta);
+        self.conversation.focus = focus.min(len.saturating_sub(1));
+        self.conversation.scroll_to_focus = true;
+    }
+
+    /// Expand or collapse the focused message
+    pub fn toggle_message(&mut self) {
+        self.sync_conversation();
+        if let Some(key) = self.open_email().map(|e| e.key()) {
+            if !self.conversation.expanded.remove(&key) {
+                self.conversation.expanded.insert(key);
+            }
+            self.conversation.scroll_to_focus = true;
         }
     }
 
-    pub fn selected_email(&self) -> Option<&Email> {
-        self.list_state.selected().and_then(|i| self.emails.get(i))
+    /// Expand every message, or collapse them all if they already are
+    pub fn toggle_all_messages(&mut self) {
+        self.sync_conversation();
+        let keys: Vec<MessageKey> = self.selected_thread().iter().map(|e| e.key()).collect();
+        if keys.iter().all(|k| self.conversation.expanded.contains(k)) {
+            self.conversation.expanded.clear();
+        } else {
+            self.conversation.expanded.extend(keys);
+        }
+        self.conversation.scroll_to_focus = true;
     }
 
     fn get_visible_indices(&self) -> Vec<usize> {
@@ -751,7 +1721,7 @@ impl App {
         };
         
         // Apply importance filter
-        base_indices
+        let indices = base_indices
             .into_iter()
             .filter(|&i| {
                 if let Some(email) = self.emails.get(i) {
@@ -763,8 +1733,15 @@ impl App {
                 } else {
                     false
                 }
-            })
-            .collect()
+            });
+
+        if !self.threaded {
+            return indices.collect();
+        }
+        // One row per conversation, its newest visible message
+        let roots = conversations(&self.emails);
+        let mut shown = std::collections::HashSet::new();
+        indices.filter(|&i| shown.insert(roots[i])).collect()
     }
 
     pub fn select_next(&mut self) {
@@ -784,6 +1761,11 @@ impl App {
         self.list_state.select(Some(indices[current_pos.saturating_sub(1)]));
     }
 
+    pub fn at_last_row(&self) -> bool {
+        let indices = self.get_visible_indices();
+        indices.last().is_some() && indices.last().copied() == self.list_state.selected()
+    }
+
     pub fn select_first(&mut self) {
         let indices = self.get_visible_indices();
         if !indices.is_empty() {
@@ -805,14 +1787,6 @@ impl App {
     }
 
     // Scrolling
-    pub fn scroll_down(&mut self) {
-        self.scroll_offset = self.scroll_offset.saturating_add(1);
-    }
-
-    pub fn scroll_up(&mut self) {
-        self.scroll_offset = self.scroll_offset.saturating_sub(1);
-    }
-
     pub fn half_page_down(&mut self, height: u16) {
         self.scroll_offset = self.scroll_offset.saturating_add(height / 2);
     }
@@ -850,15 +1824,41 @@ impl App {
                 self.render_help(frame);
             }
             View::Remind => {
-                self.render_inbox(frame, main_area);
+                match self.remind.target {
+                    RemindTarget::Reschedule(_) => self.render_reminders(frame, main_area),
+                    RemindTarget::RescheduleSend(_) => self.render_outbox(frame, main_area),
+                    RemindTarget::SendLater => self.render_compose(frame, main_area),
+                    RemindTarget::Selected | RemindTarget::FollowUp => self.render_inbox(frame, main_area),
+                }
                 self.render_remind_popup(frame);
             }
+            View::Reminders => self.render_reminders(frame, main_area),
+            View::Outbox => self.render_outbox(frame, main_area),
+            View::Picker => {
+                if self.picker.from == View::EmailView {
+                    self.render_email_view(frame, main_area);
+                } else {
+                    self.render_inbox(frame, main_area);
+                }
+                self.render_picker(frame);
+            }
         }
 
         self.render_status_bar(frame, area);
     }
 
     fn render_inbox(&mut self, frame: &mut Frame, area: Rect) {
+        let area = if self.sidebar.visible {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Length(SIDEBAR_WIDTH), Constraint::Min(0)])
+                .split(area);
+            self.render_sidebar(frame, columns[0]);
+            columns[1]
+        } else {
+            area
+        };
+
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([Constraint::Min(0), Constraint::Length(1)])
@@ -866,30 +1866,49 @@ impl App {
 
         let visible_indices = self.get_visible_indices();
         let width = chunks[0].width as usize;
+        let folder_name = self
+            .folder_name(self.active_account, &self.current_folder)
+            .unwrap_or_default();
+        let show_account = self.current_folder == Folder::AllInboxes;
+        let account_width = if show_account { 11 } else { 0 };
+
+        // Size, unread and starred state of each conversation, by its first index
+        let roots = if self.threaded { conversations(&self.emails) } else { vec![] };
+        let mut threads: std::collections::HashMap<usize, (usize, bool, bool)> = std::collections::HashMap::new();
+        for (email, root) in self.emails.iter().zip(&roots) {
+            let thread = threads.entry(*root).or_insert((0, false, false));
+            thread.0 += 1;
+            thread.1 |= !email.seen;
+            thread.2 |= email.flagged;
+        }
         
-        let items: Vec<ListItem> = visible_indices
+        let mut items: Vec<ListItem> = visible_indices
             .iter()
             .filter_map(|&i| self.emails.get(i).map(|e| (i, e)))
-            .map(|(_idx, email)| {
-                let is_starred = self.starred.contains(&email.uid);
-                let is_selected = self.selected.contains(&email.uid);
+            .map(|(idx, email)| {
+                let (count, unread, is_starred) = roots
+                    .get(idx)
+                    .and_then(|root| threads.get(root))
+                    .copied()
+                    .unwrap_or((1, !email.seen, email.flagged));
+                let is_selected = self.selected.contains(&email.key());
                 let star = if is_starred { "★" } else { " " };
                 let important_marker = if email.important { "!" } else { " " };
-                let select_marker = if is_selected { "▌" } else if email.seen { "  " } else { "● " };
+                let select_marker = if is_selected { "▌" } else if unread { "● " } else { "  " };
                 let time = relative_time(email.date);
                 
                 // Calculate available space for subject
                 let from_width = 22;
                 let time_width = time.chars().count() + 2;
-                let fixed_width = 7 + from_width + time_width; // +1 for importance marker
+                let fixed_width = 7 + account_width + from_width + time_width; // +1 for importance marker
                 let subject_width = width.saturating_sub(fixed_width);
                 
                 let style = if is_selected {
                     Style::default().fg(self.theme.accent).add_modifier(Modifier::BOLD)
-                } else if email.seen {
-                    self.theme.text_dim()
-                } else {
+                } else if unread {
                     self.theme.unread()
+                } else {
+                    self.theme.text_dim()
                 };
 
                 let marker_style = if is_selected {
@@ -898,17 +1917,51 @@ impl App {
                     self.theme.accent()
                 };
 
+                let account_tag = if show_account {
+                    let name = self.accounts.get(email.account).map(|a| a.name.as_str()).unwrap_or("");
+                    format!("{:<width$}", truncate(name, account_width - 2), width = account_width)
+                } else {
+                    String::new()
+                };
+
+                let from = if count > 1 {
+                    format!("{} ({})", truncate(&email.from, from_width - 6), count)
+                } else {
+                    truncate(&email.from, from_width)
+                };
+
+                // Labels go before the subject while it keeps half its room
+                let mut chips = String::new();
+                for label in label_chips(&email.labels, &folder_name) {
+                    let chip = format!("[{}] ", truncate(&label, 16));
+                    if (chips.chars().count() + chip.chars().count()) * 2 > subject_width {
+                        break;
+                    }
+                    chips.push_str(&chip);
+                }
+                let subject_width = subject_width.saturating_sub(chips.chars().count());
+
                 ListItem::new(Line::from(vec![
                     Span::styled(select_marker, marker_style),
                     Span::styled(star, Style::default().fg(self.theme.warning)),
                     Span::styled(important_marker, Style::default().fg(self.theme.error)),
-                    Span::styled(format!("{:<width$}", truncate(&email.from, from_width), width = from_width), style),
+                    Span::styled(account_tag, Style::default().fg(self.theme.accent_dim)),
+                    Span::styled(format!("{:<width$}", from, width = from_width), style),
+                    Span::styled(chips, Style::default().fg(self.theme.accent_dim)),
                     Span::styled(truncate(&email.subject, subject_width), style),
                     Span::styled(format!("  {}", time), self.theme.text_muted()),
                 ]))
             })
             .collect();
 
+        let loading_more = self.paging.pending > 0;
+        if loading_more {
+            items.push(ListItem::new(Line::from(Span::styled(
+                "   Loading more…",
+                self.theme.text_muted(),
+            ))));
+        }
+
         // Map selection
         let selected_actual = self.list_state.selected();
         let visible_selected = selected_actual.and_then(|sel| {
@@ -922,10 +1975,17 @@ impl App {
             ImportanceFilter::Important => " [Important]",
             ImportanceFilter::NotImportant => " [Not Important]",
         };
+        let threads_suffix = if self.threaded { " [Conversations]" } else { "" };
         let title = if self.search.active {
-            format!(" {} · \"{}\"{} ", self.current_folder.display_name(), self.search.query, filter_suffix)
+            format!(
+                " {} · \"{}\"{}{} ",
+                self.current_folder.display_name(),
+                self.search.query,
+                filter_suffix,
+                threads_suffix
+            )
         } else {
-            format!(" {}{} ", self.current_folder.display_name(), filter_suffix)
+            format!(" {}{}{} ", self.current_folder.display_name(), filter_suffix, threads_suffix)
         };
 
         let list = List::new(items)
@@ -938,12 +1998,89 @@ impl App {
                     .padding(Padding::horizontal(1))
             )
             .highlight_style(self.theme.selected())
-            .highlight_symbol("  ");
+            .highlight_symbol("  ")
+            // Keep the loading row in view below the last message
+            .scroll_padding(usize::from(loading_more));
 
         frame.render_stateful_widget(list, chunks[0], &mut visible_list_state);
     }
 
+    /// The active account's folders, nested by their delimiter, with unread counts
+    fn render_sidebar(&self, frame: &mut Frame, area: Rect) {
+        let folders = self.sidebar_folders();
+        let current = self.folder_name(self.active_account, &self.current_folder);
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(area);
+        // Inside the border and padding
+        let width = (chunks[0].width as usize).saturating_sub(4);
+
+        let mut items: Vec<ListItem> = folders
+            .iter()
+            .map(|folder| {
+                let (depth, leaf) = match &folder.delimiter {
+                    Some(delim) if !delim.is_empty() => {
+                        let parts: Vec<&str> = folder.name.split(delim.as_str()).collect();
+                        (parts.len() - 1, parts[parts.len() - 1])
+                    }
+                    _ => (0, folder.name.as_str()),
+                };
+                let indent = "  ".repeat(depth);
+                let count = match folder.unread {
+                    Some(n) if n > 0 => n.to_string(),
+                    _ => String::new(),
+                };
+                let name_width = width.saturating_sub(indent.len() + count.len() + 1);
+                let leaf = utf7::decode(leaf);
+                let style = if Some(&folder.name) == current.as_ref() {
+                    self.theme.accent()
+                } else if folder.unread.unwrap_or(0) > 0 {
+                    self.theme.unread()
+                } else {
+                    self.theme.text_dim()
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!("{}{:<w$} ", indent, truncate(&leaf, name_width), w = name_width),
+                        style,
+                    ),
+                    Span::styled(count, self.theme.text_muted()),
+                ]))
+            })
+            .collect();
+        if items.is_empty() {
+            items.push(ListItem::new(Span::styled("Loading…", self.theme.text_muted())));
+        }
+
+        let mut list_state = ListState::default();
+        if self.sidebar.focused && !folders.is_empty() {
+            list_state.select(Some(self.sidebar.selected.min(folders.len() - 1)));
+        }
+
+        let border = if self.sidebar.focused {
+            Style::default().fg(self.theme.accent)
+        } else {
+            self.theme.border()
+        };
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(border)
+                    .title(" Folders ")
+                    .title_style(self.theme.accent())
+                    .padding(Padding::horizontal(1))
+            )
+            .highlight_style(self.theme.selected());
+
+        frame.render_stateful_widget(list, chunks[0], &mut list_state);
+    }
+
     fn render_email_view(&mut self, frame: &mut Frame, area: Rect) {
+        if self.selected_thread().len() > 1 {
+            return self.render_conversation(frame, area);
+        }
         let Some(email) = self.selected_email().cloned() else { return };
         
         let chunks = Layout::default()
@@ -966,6 +2103,17 @@ impl App {
             ]),
             Line::from(vec![
                 Span::styled(time_full, self.theme.text_muted()),
+                Span::styled(
+                    if email.has_attachments { "  · attachments" } else { "" },
+                    self.theme.text_muted(),
+                ),
+                Span::styled(
+                    label_chips(&email.labels, "")
+                        .iter()
+                        .map(|label| format!("  [{}]", label))
+                        .collect::<String>(),
+                    Style::default().fg(self.theme.accent_dim),
+                ),
             ]),
             Line::from(""),
         ];
@@ -975,10 +2123,14 @@ impl App {
         frame.render_widget(header, chunks[0]);
 
         // Body - clean, no boxes
-        let body_lines: Vec<Line> = email.body
-            .lines()
-            .map(|line| Line::from(Span::styled(line.to_string(), self.theme.text())))
-            .collect();
+        let body_lines: Vec<Line> = if email.body_loaded {
+            email.body
+                .lines()
+                .map(|line| Line::from(Span::styled(line.to_string(), self.theme.text())))
+                .collect()
+        } else {
+            vec![Line::from(Span::styled("Loading…", self.theme.text_muted()))]
+        };
 
         let body = Paragraph::new(body_lines)
             .block(
@@ -993,6 +2145,107 @@ impl App {
         frame.render_widget(body, chunks[1]);
     }
 
+    /// A whole conversation, oldest first, collapsed messages as one line
+    fn render_conversation(&mut self, frame: &mut Frame, area: Rect) {
+        self.sync_conversation();
+        let thread: Vec<Email> = self.selected_thread().into_iter().cloned().collect();
+        let focus = self.conversation_focus(thread.len());
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(5), Constraint::Min(0)])
+            .split(area);
+
+        let subject = thread.last().map(|e| e.subject.as_str()).unwrap_or_default();
+        let mut labels: Vec<String> = Vec::new();
+        for email in &thread {
+            for label in label_chips(&email.labels, "") {
+                if !labels.contains(&label) {
+                    labels.push(label);
+                }
+            }
+        }
+        let header = Paragraph::new(vec![
+            Line::from(""),
+            Line::from(Span::styled(subject, self.theme.text().add_modifier(Modifier::BOLD))),
+            Line::from(vec![
+                Span::styled(format!("{} messages", thread.len()), self.theme.text_muted()),
+                Span::styled(
+                    labels.iter().map(|label| format!("  [{}]", label)).collect::<String>(),
+                    Style::default().fg(self.theme.accent_dim),
+                ),
+            ]),
+            Line::from(""),
+        ])
+        .block(Block::default().padding(Padding::horizontal(2)));
+        frame.render_widget(header, chunks[0]);
+
+        // Inside the border and padding of the body block
+        let width = chunks[1].width.saturating_sub(4).max(1) as usize;
+        let mut lines: Vec<Line> = Vec::new();
+        let mut focus_line = 0;
+        for (i, email) in thread.iter().enumerate() {
+            if i == focus {
+                focus_line = lines.iter().map(|l| l.width().max(1).div_ceil(width)).sum::<usize>();
+            }
+            let expanded = self.conversation.expanded.contains(&email.key());
+            let name_style = if i == focus {
+                self.theme.accent().add_modifier(Modifier::BOLD)
+            } else if email.seen {
+                self.theme.text_dim()
+            } else {
+                self.theme.unread()
+            };
+            let time = email.date
+                .map(|d| d.format("%b %d, %H:%M").to_string())
+                .unwrap_or_default();
+            lines.push(Line::from(vec![
+                Span::styled(if expanded { "▾ " } else { "▸ " }, self.theme.accent()),
+                Span::styled(email.from.clone(), name_style),
+                Span::styled(format!("  {}", time), self.theme.text_muted()),
+                Span::styled(
+                    if email.has_attachments { "  · attachments" } else { "" },
+                    self.theme.text_muted(),
+                ),
+            ]));
+
+            if !email.body_loaded {
+                lines.push(Line::from(Span::styled("  Loading…", self.theme.text_muted())));
+            } else if expanded {
+                lines.push(Line::from(""));
+                lines.extend(
+                    email.body
+                        .lines()
+                        .map(|line| Line::from(Span::styled(line.to_string(), self.theme.text()))),
+                );
+            } else {
+                let preview = email.body.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("");
+                lines.push(Line::from(Span::styled(
+                    format!("  {}", truncate(preview, width.saturating_sub(2))),
+                    self.theme.text_muted(),
+                )));
+            }
+            lines.push(Line::from(""));
+        }
+
+        if self.conversation.scroll_to_focus {
+            self.conversation.scroll_to_focus = false;
+            self.scroll_offset = u16::try_from(focus_line).unwrap_or(u16::MAX);
+        }
+
+        let body = Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::TOP)
+                    .border_style(self.theme.border())
+                    .padding(Padding::new(2, 2, 1, 1))
+            )
+            .wrap(Wrap { trim: false })
+            .scroll((self.scroll_offset, 0));
+
+        frame.render_widget(body, chunks[1]);
+    }
+
     fn render_compose(&mut self, frame: &mut Frame, area: Rect) {
         let has_chain = !self.compose.reply_chain.is_empty();
         
@@ -1152,7 +2405,7 @@ impl App {
                 Block::default()
                     .borders(Borders::ALL)
                     .border_style(self.theme.accent())
-                    .title(" Search ")
+                    .title(" Search · Ctrl+s the whole mailbox ")
                     .padding(Padding::horizontal(1))
             );
         frame.render_widget(search_input, chunks[0]);
@@ -1242,6 +2495,75 @@ impl App {
         }
     }
 
+    fn render_picker(&self, frame: &mut Frame) {
+        let area = frame.area();
+        let width = (area.width as f32 * 0.4) as u16;
+        let shown = self.picker.matches.len().clamp(1, 10) as u16;
+        let popup = Rect::new((area.width - width) / 2, area.height / 4, width, 3 + shown);
+        frame.render_widget(Clear, popup);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0)])
+            .split(popup);
+
+        let targets = &self.picker.targets;
+        let title = match (self.picker.kind, targets.len()) {
+            (PickerKind::Label, 1) => " Label ".to_string(),
+            (PickerKind::Label, n) => format!(" Label {} messages ", n),
+            (PickerKind::Move, 1) => " Move to ".to_string(),
+            (PickerKind::Move, n) => format!(" Move {} messages to ", n),
+        };
+        let input = Paragraph::new(self.picker.query.as_str()).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(self.theme.accent())
+                .title(title)
+                .title_style(self.theme.accent()),
+        );
+        frame.render_widget(input, chunks[0]);
+
+        let items: Vec<ListItem> = if self.picker.matches.is_empty() {
+            let hint = if self.picker.kind == PickerKind::Move {
+                " No such folder".to_string()
+            } else if self.picker.query.trim().is_empty() {
+                " No labels".to_string()
+            } else {
+                format!(" Enter: new label \"{}\"", self.picker.query.trim())
+            };
+            vec![ListItem::new(Span::styled(hint, self.theme.text_muted()))]
+        } else {
+            self.picker
+                .matches
+                .iter()
+                .enumerate()
+                .map(|(i, &option)| {
+                    let label = &self.picker.options[option];
+                    // A label on all of them gets a check, on some a dot
+                    let mark = match self.picker.kind {
+                        PickerKind::Move => "  ",
+                        PickerKind::Label => match self.label_count(targets, label) {
+                            0 => "  ",
+                            n if n == targets.len() => "✓ ",
+                            _ => "· ",
+                        },
+                    };
+                    let style = if i == self.picker.selected {
+                        self.theme.selected()
+                    } else {
+                        Style::default()
+                    };
+                    ListItem::new(Line::from(vec![
+                        Span::styled(format!(" {}", mark), self.theme.accent()),
+                        Span::styled(utf7::decode(label), style),
+                    ]))
+                })
+                .collect()
+        };
+        let list = List::new(items).style(Style::default().bg(self.theme.bg));
+        frame.render_widget(list, chunks[1]);
+    }
+
     fn render_help(&self, frame: &mut Frame) {
         let area = frame.area();
         let popup = centered_rect(50, 60, area);
@@ -1255,11 +2577,18 @@ impl App {
             Line::from(vec![Span::styled("j/k       ", self.theme.accent()), Span::raw("Navigate emails")]),
             Line::from(vec![Span::styled("Enter/l   ", self.theme.accent()), Span::raw("Open email")]),
             Line::from(vec![Span::styled("J/K       ", self.theme.accent()), Span::raw("Select multiple")]),
+            Line::from(vec![Span::styled("t         ", self.theme.accent()), Span::raw("Toggle conversations")]),
+            Line::from(vec![Span::styled("b         ", self.theme.accent()), Span::raw("Folder sidebar, Tab to focus")]),
+            Line::from(vec![Span::styled("L         ", self.theme.accent()), Span::raw("Labels (Gmail), l in email view")]),
+            Line::from(vec![Span::styled("m         ", self.theme.accent()), Span::raw("Move to folder")]),
             Line::from(""),
             Line::from(Span::styled("Email View:", self.theme.text_dim())),
             Line::from(vec![Span::styled("j/k       ", self.theme.accent()), Span::raw("Next/previous email")]),
             Line::from(vec![Span::styled("Space     ", self.theme.accent()), Span::raw("Scroll down")]),
             Line::from(vec![Span::styled("Shift+Spc ", self.theme.accent()), Span::raw("Scroll up")]),
+            Line::from(vec![Span::styled("n/p       ", self.theme.accent()), Span::raw("Next/previous message")]),
+            Line::from(vec![Span::styled("o/O       ", self.theme.accent()), Span::raw("Expand message / all")]),
+            Line::from(vec![Span::styled("l         ", self.theme.accent()), Span::raw("Labels (Gmail), L in inbox view")]),
             Line::from(""),
             Line::from(Span::styled("All Views:", self.theme.text_dim())),
             Line::from(vec![Span::styled("c         ", self.theme.accent()), Span::raw("Compose")]),
@@ -1270,11 +2599,16 @@ impl App {
             Line::from(vec![Span::styled("d         ", self.theme.accent()), Span::raw("Delete")]),
             Line::from(vec![Span::styled("s         ", self.theme.accent()), Span::raw("Star/unstar")]),
             Line::from(vec![Span::styled("h         ", self.theme.accent()), Span::raw("Remind email")]),
+            Line::from(vec![Span::styled("F         ", self.theme.accent()), Span::raw("Follow up on last sent")]),
             Line::from(vec![Span::styled("/         ", self.theme.accent()), Span::raw("Search")]),
             Line::from(vec![Span::styled(":         ", self.theme.accent()), Span::raw("Command palette")]),
             Line::from(vec![Span::styled("R         ", self.theme.accent()), Span::raw("Refresh")]),
             Line::from(vec![Span::styled("gi/gt/gd/ge/ga", self.theme.accent()), Span::raw("Go to folders")]),
+            Line::from(vec![Span::styled("g1-g9     ", self.theme.accent()), Span::raw("Switch account")]),
+            Line::from(vec![Span::styled("gu        ", self.theme.accent()), Span::raw("All inboxes")]),
             Line::from(vec![Span::styled("Ctrl+s    ", self.theme.accent()), Span::raw("Send (in compose)")]),
+            Line::from(vec![Span::styled("Ctrl+l    ", self.theme.accent()), Span::raw("Send later (in compose)")]),
+            Line::from(vec![Span::styled("u         ", self.theme.accent()), Span::raw("Undo send, archive, delete, move")]),
             Line::from(vec![Span::styled("q/Esc     ", self.theme.accent()), Span::raw("Go back / Quit")]),
         ];
 
@@ -1292,10 +2626,135 @@ impl App {
         frame.render_widget(help, popup);
     }
 
+    fn render_reminders(&self, frame: &mut Frame, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(area);
+
+        let width = chunks[0].width as usize;
+        let show_account = self.accounts.len() > 1;
+        let mut items: Vec<ListItem> = self.reminders.items
+            .iter()
+            .map(|reminder| {
+                let time = reminder.return_time
+                    .with_timezone(&chrono::Local)
+                    .format("%a, %b %d %H:%M")
+                    .to_string();
+                let account = if show_account {
+                    format!("{:<24}", truncate(&reminder.account, 22))
+                } else {
+                    String::new()
+                };
+                let from_width = 22;
+                let from = match reminder.kind {
+                    ReminderKind::Snooze => reminder.from.clone(),
+                    ReminderKind::FollowUp => format!("→ {}", reminder.to),
+                };
+                let subject_width = width
+                    .saturating_sub(time.chars().count() + account.chars().count() + from_width + 8);
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{}   ", time), self.theme.accent()),
+                    Span::styled(account, self.theme.text_dim()),
+                    Span::styled(
+                        format!("{:<width$}", truncate(&from, from_width - 2), width = from_width),
+                        self.theme.text_dim(),
+                    ),
+                    Span::styled(truncate(&reminder.subject, subject_width), self.theme.text()),
+                ]))
+            })
+            .collect();
+        if items.is_empty() {
+            items.push(ListItem::new(Span::styled("No reminders", self.theme.text_muted())));
+        }
+
+        let mut list_state = ListState::default();
+        if !self.reminders.items.is_empty() {
+            list_state.select(Some(self.reminders.selected));
+        }
+
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(self.theme.border())
+                    .title(" Reminders · r reschedule · d cancel ")
+                    .title_style(self.theme.accent())
+                    .padding(Padding::horizontal(1))
+            )
+            .highlight_style(self.theme.selected())
+            .highlight_symbol("  ");
+
+        frame.render_stateful_widget(list, chunks[0], &mut list_state);
+    }
+
+    fn render_outbox(&self, frame: &mut Frame, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(area);
+
+        let width = chunks[0].width as usize;
+        let show_account = self.accounts.len() > 1;
+        let mut items: Vec<ListItem> = self.outbox.items
+            .iter()
+            .map(|queued| {
+                let time = queued.send_at
+                    .with_timezone(&chrono::Local)
+                    .format("%a, %b %d %H:%M")
+                    .to_string();
+                let account = if show_account {
+                    format!("{:<24}", truncate(&queued.account, 22))
+                } else {
+                    String::new()
+                };
+                let to_width = 22;
+                let subject_width = width
+                    .saturating_sub(time.chars().count() + account.chars().count() + to_width + 8);
+                let style = if self.sending.contains(&queued.id) {
+                    self.theme.text_muted()
+                } else {
+                    self.theme.text()
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{}   ", time), self.theme.accent()),
+                    Span::styled(account, self.theme.text_dim()),
+                    Span::styled(
+                        format!("{:<width$}", truncate(&format!("→ {}", queued.email.to), to_width - 2), width = to_width),
+                        self.theme.text_dim(),
+                    ),
+                    Span::styled(truncate(&queued.email.subject, subject_width), style),
+                ]))
+            })
+            .collect();
+        if items.is_empty() {
+            items.push(ListItem::new(Span::styled("Nothing scheduled", self.theme.text_muted())));
+        }
+
+        let mut list_state = ListState::default();
+        if !self.outbox.items.is_empty() {
+            list_state.select(Some(self.outbox.selected));
+        }
+
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(self.theme.border())
+                    .title(" Outbox · e edit · r reschedule · d cancel ")
+                    .title_style(self.theme.accent())
+                    .padding(Padding::horizontal(1))
+            )
+            .highlight_style(self.theme.selected())
+            .highlight_symbol("  ");
+
+        frame.render_stateful_widget(list, chunks[0], &mut list_state);
+    }
+
     fn render_remind_popup(&self, frame: &mut Frame) {
         let area = frame.area();
         let width = 50u16;
-        let height = 7u16;
+        let height = 8u16;
         
         let popup = Rect::new(
             (area.width - width) / 2,
@@ -1316,14 +2775,33 @@ impl App {
                 Block::default()
                     .borders(Borders::ALL)
                     .border_style(self.theme.accent())
-                    .title(" When? ")
+                    .title(match self.remind.target {
+                        RemindTarget::FollowUp => " Remind me if nobody replies by ",
+                        RemindTarget::SendLater | RemindTarget::RescheduleSend(_) => " Send at ",
+                        _ => " When? ",
+                    })
             )
             .style(Style::default().bg(self.theme.bg));
         frame.render_widget(input, chunks[0]);
 
+        // What the input resolves to, updated as it is typed
+        let preview = if self.remind.input.trim().is_empty() {
+            Line::from("")
+        } else {
+            match reminders::parse_when(&self.remind.input, chrono::Local::now(), &self.snooze) {
+                Ok(at) => Line::from(Span::styled(
+                    format!("→ {}", at.format("%a, %b %d %Y at %H:%M")),
+                    self.theme.success(),
+                )),
+                Err(e) => Line::from(Span::styled(e.to_string(), self.theme.text_muted())),
+            }
+        };
+
         let help_text = vec![
+            preview,
+            Line::from(""),
             Line::from(Span::styled("Examples:", self.theme.text_dim())),
-            Line::from("1 hour   1 day   2 weeks"),
+            Line::from("tomorrow 9am   monday   in 3h   tonight"),
         ];
         
         let help = Paragraph::new(help_text)
@@ -1334,7 +2812,7 @@ impl App {
     fn render_go_menu(&self, frame: &mut Frame) {
         let area = frame.area();
         let width = 30u16;
-        let height = 9u16;
+        let height = if self.accounts.len() > 1 { 12u16 } else { 10u16 };
         
         let popup = Rect::new(
             (area.width - width) / 2,
@@ -1345,7 +2823,7 @@ impl App {
 
         frame.render_widget(Clear, popup);
         
-        let menu_text = vec![
+        let mut menu_text = vec![
             Line::from(Span::styled("Go to:", self.theme.accent().add_modifier(Modifier::BOLD))),
             Line::from(""),
             Line::from(vec![Span::styled("g ", self.theme.accent()), Span::raw("Top of inbox")]),
@@ -1354,7 +2832,15 @@ impl App {
             Line::from(vec![Span::styled("d ", self.theme.accent()), Span::raw("Drafts")]),
             Line::from(vec![Span::styled("e ", self.theme.accent()), Span::raw("Trash")]),
             Line::from(vec![Span::styled("a ", self.theme.accent()), Span::raw("Archive")]),
+            Line::from(vec![Span::styled("s ", self.theme.accent()), Span::raw("Starred")]),
         ];
+        if self.accounts.len() > 1 {
+            menu_text.push(Line::from(vec![Span::styled("u ", self.theme.accent()), Span::raw("All inboxes")]));
+            menu_text.push(Line::from(vec![
+                Span::styled(format!("1-{} ", self.accounts.len().min(9)), self.theme.accent()),
+                Span::raw("Switch account"),
+            ]));
+        }
         
         let menu = Paragraph::new(menu_text)
             .block(
@@ -1371,9 +2857,27 @@ impl App {
     fn render_status_bar(&self, frame: &mut Frame, area: Rect) {
         let status_area = Rect::new(0, area.height - 1, area.width, 1);
 
-        let (left, right) = if let Some(notif) = &self.notification {
+        let activity = self.tasks.first().map(|task| {
+            let more = if self.tasks.len() > 1 {
+                format!(" (+{})", self.tasks.len() - 1)
+            } else {
+                String::new()
+            };
+            Span::styled(format!(" ⟳ {}{} ", task.label, more), self.theme.accent())
+        });
+
+        let (left, right) = if let Some(pending) = &self.pending_send {
+            let left = pending.send_at.saturating_duration_since(std::time::Instant::now());
+            (
+                Span::styled(
+                    format!(" Sending in {}s · u to undo", left.as_secs() + 1),
+                    self.theme.accent(),
+                ),
+                activity.unwrap_or_default(),
+            )
+        } else if let Some(notif) = &self.notification {
             let style = if notif.is_error { self.theme.error() } else { self.theme.success() };
-            (Span::styled(&notif.message, style), Span::raw(""))
+            (Span::styled(&notif.message, style), activity.unwrap_or_default())
         } else {
             let mode = match self.view {
                 View::Compose => {
@@ -1385,19 +2889,30 @@ impl App {
                 _ => "",
             };
             
-            let left = format!(" {} · {} emails", self.current_folder.display_name(), self.emails.len());
-            let right = if mode.is_empty() {
-                " ? help ".to_string()
+            let left = format!(
+                " {} · {} · {} emails",
+                self.active_account_name(),
+                self.current_folder.display_name(),
+                self.emails.len()
+            );
+            let right = if let Some(activity) = activity {
+                activity
+            } else if mode.is_empty() {
+                Span::styled(" ? help ", self.theme.text_muted())
             } else {
-                format!(" {} ", mode)
+                Span::styled(format!(" {} ", mode), self.theme.text_muted())
             };
             
-            (Span::styled(left, self.theme.text_muted()), Span::styled(right, self.theme.text_muted()))
+            (Span::styled(left, self.theme.text_muted()), right)
         };
 
         let status = Paragraph::new(Line::from(vec![left]))
             .style(Style::default().bg(self.theme.selection));
         frame.render_widget(status, status_area);
+
+        let hint = Paragraph::new(Line::from(vec![right]))
+            .alignment(Alignment::Right);
+        frame.render_widget(hint, status_area);
     }
 }
 
@@ -1,5 +1,6 @@
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-use super::app::{App, ComposeField, EditMode, Folder, View, VimOperator};
+use super::app::{App, ComposeField, EditMode, Folder, PickerKind, RemindState, RemindTarget, View, VimOperator};
+use crate::email::{utf7, MessageKey};
 
 pub enum Action {
     None,
@@ -9,10 +10,34 @@ pub enum Action {
     EditDraft,
     DeleteEmail,
     ArchiveEmail,
-    MarkAsRead(u32),
+    MarkAsRead(Vec<MessageKey>),
+    ToggleStar(MessageKey),
     ChangeFolder(Folder),
+    SwitchAccount(usize),
     FetchThread,
-    RemindEmail(u32, String),
+    RemindEmail(MessageKey, String),
+    /// Fetch the page below the last listed message
+    LoadMore,
+    /// Drop a reminder, leaving its message in the archive
+    CancelReminder(u64),
+    RescheduleReminder(u64, String),
+    /// Remind of the last sent message if nobody has replied by then
+    FollowUp(String),
+    /// Queue the message being composed for later
+    SendLater(String),
+    /// Open a queued message in compose
+    EditQueued(u64),
+    RescheduleQueued(u64, String),
+    CancelQueued(u64),
+    /// Take back the message being sent, or else the last operation
+    Undo,
+    /// Fetch the folder list and unread counts for the sidebar
+    ListFolders,
+    /// Move the picker's messages to a folder
+    MoveTo(String),
+    /// Put a Gmail label on the picker's messages, or take it off when
+    /// they all have it
+    ToggleLabel(String),
 }
 
 pub fn handle_key_event(app: &mut App, key: KeyEvent, view_height: u16) -> Action {
@@ -27,10 +52,17 @@ pub fn handle_key_event(app: &mut App, key: KeyEvent, view_height: u16) -> Actio
         View::Search => handle_search_keys(app, key),
         View::Command => handle_command_keys(app, key),
         View::Remind => handle_remind_keys(app, key),
+        View::Reminders => handle_reminders_keys(app, key),
+        View::Outbox => handle_outbox_keys(app, key),
+        View::Picker => handle_picker_keys(app, key),
     }
 }
 
 fn handle_inbox_keys(app: &mut App, key: KeyEvent) -> Action {
+    if app.sidebar.focused {
+        return handle_sidebar_keys(app, key);
+    }
+
     // Handle pending 'g' commands
     if let Some(pending) = app.pending_command {
         if key.code == KeyCode::Esc {
@@ -63,6 +95,21 @@ fn handle_inbox_keys(app: &mut App, key: KeyEvent) -> Action {
                 app.clear_search_filter();
                 return Action::ChangeFolder(Folder::Archive);
             }
+            ('g', KeyCode::Char('s')) => {
+                app.clear_search_filter();
+                return Action::ChangeFolder(Folder::Starred);
+            }
+            ('g', KeyCode::Char('u')) => {
+                app.clear_search_filter();
+                return Action::ChangeFolder(Folder::AllInboxes);
+            }
+            ('g', KeyCode::Char(c @ '1'..='9')) => {
+                let idx = c as usize - '1' as usize;
+                if idx < app.accounts.len() {
+                    return Action::SwitchAccount(idx);
+                }
+                return Action::None;
+            }
             _ => {}
         }
     }
@@ -84,6 +131,9 @@ fn handle_inbox_keys(app: &mut App, key: KeyEvent) -> Action {
             Action::None
         }
         KeyCode::Char('j') | KeyCode::Down => {
+            if app.at_last_row() && !app.search.active {
+                return Action::LoadMore;
+            }
             app.select_next();
             Action::None
         }
@@ -102,13 +152,17 @@ fn handle_inbox_keys(app: &mut App, key: KeyEvent) -> Action {
         
         // Open email
         KeyCode::Enter | KeyCode::Char('l') => {
-            if let Some(email) = app.selected_email() {
-                let uid = email.uid;
-                let unread = !email.seen;
+            let unread: Vec<MessageKey> = app
+                .selected_thread()
+                .iter()
+                .filter(|e| !e.seen)
+                .map(|e| e.key())
+                .collect();
+            if app.selected_email().is_some() {
                 app.view = View::EmailView;
                 app.scroll_offset = 0;
-                if unread {
-                    return Action::MarkAsRead(uid);
+                if !unread.is_empty() {
+                    return Action::MarkAsRead(unread);
                 }
             }
             Action::None
@@ -116,8 +170,7 @@ fn handle_inbox_keys(app: &mut App, key: KeyEvent) -> Action {
         
         // Compose
         KeyCode::Char('c') => {
-            app.compose = Default::default();
-            app.view = View::Compose;
+            app.start_compose();
             Action::None
         }
         
@@ -136,14 +189,25 @@ fn handle_inbox_keys(app: &mut App, key: KeyEvent) -> Action {
                 Action::None
             }
         }
-        KeyCode::Char('s') => {
-            app.toggle_star();
-            Action::None
-        }
+        KeyCode::Char('s') => match app.selected_email() {
+            Some(email) => Action::ToggleStar(email.key()),
+            None => Action::None,
+        },
         KeyCode::Char('x') => {
             app.toggle_selection();
             Action::None
         }
+        KeyCode::Char('t') => {
+            app.toggle_threads();
+            Action::None
+        }
+
+        // Folder sidebar
+        KeyCode::Char('b') => toggle_sidebar(app),
+        KeyCode::Tab if app.sidebar.visible => {
+            app.sidebar.focused = true;
+            Action::None
+        }
         KeyCode::Esc => {
             if !app.selected.is_empty() {
                 app.clear_selection();
@@ -159,6 +223,19 @@ fn handle_inbox_keys(app: &mut App, key: KeyEvent) -> Action {
             }
             Action::None
         }
+        KeyCode::Char('F') => {
+            start_follow_up(app);
+            Action::None
+        }
+        KeyCode::Char('L') => {
+            app.start_label_picker();
+            Action::None
+        }
+        KeyCode::Char('m') => {
+            app.start_move_picker();
+            Action::None
+        }
+        KeyCode::Char('u') => Action::Undo,
         
         // Importance filter
         KeyCode::Char('I') => {
@@ -195,6 +272,50 @@ fn handle_inbox_keys(app: &mut App, key: KeyEvent) -> Action {
     }
 }
 
+fn handle_sidebar_keys(app: &mut App, key: KeyEvent) -> Action {
+    match key.code {
+        KeyCode::Char('q') => {
+            app.should_quit = true;
+            Action::None
+        }
+        KeyCode::Char('j') | KeyCode::Down => {
+            app.sidebar_next();
+            Action::None
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            app.sidebar_previous();
+            Action::None
+        }
+        KeyCode::Enter | KeyCode::Char('l') => {
+            app.sidebar.focused = false;
+            match app.sidebar_folder() {
+                Some(folder) => {
+                    app.clear_search_filter();
+                    Action::ChangeFolder(folder)
+                }
+                None => Action::None,
+            }
+        }
+        KeyCode::Char('b') => toggle_sidebar(app),
+        KeyCode::Esc | KeyCode::Char('h') | KeyCode::Tab => {
+            app.sidebar.focused = false;
+            Action::None
+        }
+        KeyCode::Char('R') => Action::ListFolders,
+        _ => Action::None,
+    }
+}
+
+/// Open the sidebar with fresh unread counts, or close it
+fn toggle_sidebar(app: &mut App) -> Action {
+    app.toggle_sidebar();
+    if app.sidebar.visible {
+        Action::ListFolders
+    } else {
+        Action::None
+    }
+}
+
 fn handle_email_view_keys(app: &mut App, key: KeyEvent, view_height: u16) -> Action {
     match (key.modifiers, key.code) {
         // Navigation between emails
@@ -209,6 +330,24 @@ fn handle_email_view_keys(app: &mut App, key: KeyEvent, view_height: u16) -> Act
             Action::None
         }
         
+        // Messages of a conversation
+        (_, KeyCode::Char('n')) => {
+            app.focus_message(1);
+            Action::None
+        }
+        (_, KeyCode::Char('p')) => {
+            app.focus_message(-1);
+            Action::None
+        }
+        (_, KeyCode::Char('o')) | (_, KeyCode::Enter) => {
+            app.toggle_message();
+            Action::None
+        }
+        (_, KeyCode::Char('O')) => {
+            app.toggle_all_messages();
+            Action::None
+        }
+
         // Scrolling within email
         (KeyModifiers::CONTROL, KeyCode::Char('d')) => {
             app.half_page_down(view_height);
@@ -218,22 +357,22 @@ fn handle_email_view_keys(app: &mut App, key: KeyEvent, view_height: u16) -> Act
             app.half_page_up(view_height);
             Action::None
         }
-        (_, KeyCode::Char(' ')) => {
-            app.half_page_down(view_height);
-            Action::None
-        }
         (KeyModifiers::SHIFT, KeyCode::Char(' ')) => {
             app.half_page_up(view_height);
             Action::None
         }
+        (_, KeyCode::Char(' ')) => {
+            app.half_page_down(view_height);
+            Action::None
+        }
         
         // Reply/Forward
         (_, KeyCode::Char('r')) => {
-            app.start_reply(false);
+            app.start_reply();
             Action::FetchThread
         }
         (_, KeyCode::Char('a')) => {
-            app.start_reply(true);
+            app.start_reply();
             Action::FetchThread
         }
         (_, KeyCode::Char('f')) => {
@@ -252,8 +391,17 @@ fn handle_email_view_keys(app: &mut App, key: KeyEvent, view_height: u16) -> Act
             }
         }
         (_, KeyCode::Char('d')) => Action::DeleteEmail,
-        (_, KeyCode::Char('s')) => {
-            app.toggle_star();
+        (_, KeyCode::Char('s')) => match app.open_email() {
+            Some(email) => Action::ToggleStar(email.key()),
+            None => Action::None,
+        },
+        (_, KeyCode::Char('u')) => Action::Undo,
+        (_, KeyCode::Char('l')) => {
+            app.start_label_picker();
+            Action::None
+        }
+        (_, KeyCode::Char('m')) => {
+            app.start_move_picker();
             Action::None
         }
         
@@ -274,6 +422,42 @@ fn handle_email_view_keys(app: &mut App, key: KeyEvent, view_height: u16) -> Act
     }
 }
 
+fn handle_picker_keys(app: &mut App, key: KeyEvent) -> Action {
+    match (key.modifiers, key.code) {
+        (_, KeyCode::Esc) => {
+            app.view = app.picker.from.clone();
+            Action::None
+        }
+        (_, KeyCode::Enter) => {
+            app.view = app.picker.from.clone();
+            match (app.picker.kind, app.picker.choice()) {
+                (PickerKind::Label, Some(label)) => Action::ToggleLabel(label),
+                (PickerKind::Move, Some(folder)) => Action::MoveTo(folder),
+                (_, None) => Action::None,
+            }
+        }
+        (_, KeyCode::Down | KeyCode::Tab) | (KeyModifiers::CONTROL, KeyCode::Char('n')) => {
+            app.picker.select_next();
+            Action::None
+        }
+        (_, KeyCode::Up | KeyCode::BackTab) | (KeyModifiers::CONTROL, KeyCode::Char('p')) => {
+            app.picker.select_previous();
+            Action::None
+        }
+        (_, KeyCode::Backspace) => {
+            app.picker.query.pop();
+            app.picker.update_matches();
+            Action::None
+        }
+        (_, KeyCode::Char(c)) => {
+            app.picker.query.push(c);
+            app.picker.update_matches();
+            Action::None
+        }
+        _ => Action::None,
+    }
+}
+
 fn handle_search_keys(app: &mut App, key: KeyEvent) -> Action {
     match key.code {
         KeyCode::Esc => {
@@ -301,6 +485,16 @@ fn handle_search_keys(app: &mut App, key: KeyEvent) -> Action {
             }
             Action::None
         }
+        // The whole mailbox rather than the loaded messages
+        KeyCode::Char('s') if key.modifiers == KeyModifiers::CONTROL => {
+            let query = app.search.query.trim().to_string();
+            if query.is_empty() {
+                return Action::None;
+            }
+            app.clear_search_filter();
+            app.view = View::Inbox;
+            Action::ChangeFolder(Folder::Search(query))
+        }
         KeyCode::Backspace => {
             app.search.query.pop();
             app.update_search();
@@ -316,6 +510,11 @@ fn handle_search_keys(app: &mut App, key: KeyEvent) -> Action {
 }
 
 fn execute_command(app: &mut App, cmd: &str) -> Action {
+    let (cmd, arg) = match cmd.split_once(' ') {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (cmd, ""),
+    };
+
     match cmd {
         "q" | "quit" => {
             app.should_quit = true;
@@ -344,6 +543,104 @@ fn execute_command(app: &mut App, cmd: &str) -> Action {
             app.clear_search_filter();
             Action::ChangeFolder(Folder::Archive)
         }
+        "starred" => {
+            app.clear_search_filter();
+            Action::ChangeFolder(Folder::Starred)
+        }
+        "junk" | "spam" => {
+            app.clear_search_filter();
+            Action::ChangeFolder(Folder::Junk)
+        }
+        "all" => {
+            app.clear_search_filter();
+            Action::ChangeFolder(Folder::AllInboxes)
+        }
+        "search" => {
+            if arg.is_empty() {
+                app.notify_error("Usage: :search <query>");
+                return Action::None;
+            }
+            app.clear_search_filter();
+            Action::ChangeFolder(Folder::Search(arg.to_string()))
+        }
+        "folders" => toggle_sidebar(app),
+        "folder" => {
+            if arg.is_empty() {
+                app.notify_error("Usage: :folder <name>");
+                return Action::None;
+            }
+            let name = app
+                .sidebar_folders()
+                .iter()
+                .find(|f| utf7::decode(&f.name).eq_ignore_ascii_case(arg))
+                .map(|f| f.name.clone())
+                .unwrap_or_else(|| arg.to_string());
+            app.clear_search_filter();
+            Action::ChangeFolder(app.folder_for(&name))
+        }
+        "move" => {
+            app.start_move_picker();
+            if arg.is_empty() || app.view != View::Picker {
+                return Action::None;
+            }
+            app.view = app.picker.from.clone();
+            let folder = app
+                .picker
+                .options
+                .iter()
+                .find(|f| utf7::decode(f).eq_ignore_ascii_case(arg))
+                .cloned();
+            match folder {
+                Some(folder) => Action::MoveTo(folder),
+                None => {
+                    app.notify_error(&format!("No folder {}", arg));
+                    Action::None
+                }
+            }
+        }
+        "label" => {
+            app.start_label_picker();
+            if arg.is_empty() || app.view != View::Picker {
+                return Action::None;
+            }
+            app.view = app.picker.from.clone();
+            // A label LIST knows keeps its exact name, anything else is new
+            let known = app
+                .picker
+                .options
+                .iter()
+                .find(|l| utf7::decode(l).eq_ignore_ascii_case(arg))
+                .cloned();
+            Action::ToggleLabel(known.unwrap_or_else(|| utf7::encode(arg)))
+        }
+        "threads" => {
+            app.toggle_threads();
+            Action::None
+        }
+        "reminders" => {
+            app.reminders.selected = 0;
+            app.view = View::Reminders;
+            Action::None
+        }
+        "followup" => {
+            start_follow_up(app);
+            Action::None
+        }
+        "outbox" => {
+            app.outbox.selected = 0;
+            app.view = View::Outbox;
+            Action::None
+        }
+        "account" => {
+            if arg.is_empty() {
+                Action::SwitchAccount((app.active_account + 1) % app.accounts.len())
+            } else if let Some(idx) = app.account_index(arg) {
+                Action::SwitchAccount(idx)
+            } else {
+                app.notify_error(&format!("Unknown account: {}", arg));
+                Action::None
+            }
+        }
         _ => {
             app.notify_error(&format!("Unknown command: {}", cmd));
             Action::None
@@ -362,7 +659,12 @@ fn handle_command_keys(app: &mut App, key: KeyEvent) -> Action {
             let cmd = if let Some(selected) = app.command.get_selected_command() {
                 selected.to_string()
             } else {
-                app.command.input.trim().to_lowercase()
+                // Arguments such as folder and label names keep their case
+                let input = app.command.input.trim();
+                match input.split_once(' ') {
+                    Some((name, arg)) => format!("{} {}", name.to_lowercase(), arg),
+                    None => input.to_lowercase(),
+                }
             };
             app.view = View::Inbox;
             let action = execute_command(app, &cmd);
@@ -395,6 +697,18 @@ fn handle_compose_keys(app: &mut App, key: KeyEvent) -> Action {
     if key.modifiers == KeyModifiers::CONTROL && key.code == KeyCode::Char('s') {
         return Action::SendEmail;
     }
+    if key.modifiers == KeyModifiers::CONTROL && key.code == KeyCode::Char('l') {
+        if app.compose.to.is_empty() {
+            app.notify_error("'To' field is empty");
+        } else {
+            app.remind = RemindState {
+                target: RemindTarget::SendLater,
+                ..Default::default()
+            };
+            app.view = View::Remind;
+        }
+        return Action::None;
+    }
 
     match app.compose.edit_mode {
         EditMode::Insert => handle_compose_insert(app, key),
@@ -653,8 +967,11 @@ fn handle_compose_normal(app: &mut App, key: KeyEvent) -> Action {
                     || !app.compose.subject.is_empty()
                     || !app.compose.body.is_empty();
                 
-                if has_content {
+                // An edited outbox message stays queued as it was
+                if has_content && app.compose.queued.is_none() {
                     return Action::SaveDraft;
+                } else if app.compose.queued.is_some() {
+                    app.view = View::Outbox;
                 } else {
                     app.view = View::Inbox;
                 }
@@ -679,21 +996,137 @@ fn handle_help_keys(app: &mut App, key: KeyEvent) -> Action {
     }
 }
 
+fn handle_reminders_keys(app: &mut App, key: KeyEvent) -> Action {
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down => {
+            if app.reminders.selected + 1 < app.reminders.items.len() {
+                app.reminders.selected += 1;
+            }
+            Action::None
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            app.reminders.selected = app.reminders.selected.saturating_sub(1);
+            Action::None
+        }
+        KeyCode::Char('d') | KeyCode::Char('x') => match app.selected_reminder() {
+            Some(reminder) => Action::CancelReminder(reminder.id),
+            None => Action::None,
+        },
+        KeyCode::Char('r') | KeyCode::Enter => {
+            if let Some(reminder) = app.selected_reminder() {
+                app.remind = RemindState {
+                    target: RemindTarget::Reschedule(reminder.id),
+                    ..Default::default()
+                };
+                app.view = View::Remind;
+            }
+            Action::None
+        }
+        KeyCode::Char('q') | KeyCode::Esc => {
+            app.view = View::Inbox;
+            Action::None
+        }
+        _ => Action::None,
+    }
+}
+
+fn handle_outbox_keys(app: &mut App, key: KeyEvent) -> Action {
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down => {
+            if app.outbox.selected + 1 < app.outbox.items.len() {
+                app.outbox.selected += 1;
+            }
+            Action::None
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            app.outbox.selected = app.outbox.selected.saturating_sub(1);
+            Action::None
+        }
+        KeyCode::Char('e') | KeyCode::Enter => match app.selected_queued() {
+            Some(queued) => Action::EditQueued(queued.id),
+            None => Action::None,
+        },
+        KeyCode::Char('d') | KeyCode::Char('x') => match app.selected_queued() {
+            Some(queued) => Action::CancelQueued(queued.id),
+            None => Action::None,
+        },
+        KeyCode::Char('r') => {
+            if let Some(queued) = app.selected_queued() {
+                app.remind = RemindState {
+                    target: RemindTarget::RescheduleSend(queued.id),
+                    ..Default::default()
+                };
+                app.view = View::Remind;
+            }
+            Action::None
+        }
+        KeyCode::Char('q') | KeyCode::Esc => {
+            app.view = View::Inbox;
+            Action::None
+        }
+        _ => Action::None,
+    }
+}
+
+/// Open the Remind popup for the last sent message
+fn start_follow_up(app: &mut App) {
+    if app.last_sent.is_some() {
+        app.remind = RemindState {
+            target: RemindTarget::FollowUp,
+            ..Default::default()
+        };
+        app.view = View::Remind;
+    } else {
+        app.notify_error("Nothing sent yet");
+    }
+}
+
 fn handle_remind_keys(app: &mut App, key: KeyEvent) -> Action {
+    let back = match app.remind.target {
+        RemindTarget::Reschedule(_) => View::Reminders,
+        RemindTarget::RescheduleSend(_) => View::Outbox,
+        RemindTarget::SendLater => View::Compose,
+        RemindTarget::Selected | RemindTarget::FollowUp => View::Inbox,
+    };
     match key.code {
         KeyCode::Esc => {
-            app.view = View::Inbox;
+            app.remind = Default::default();
+            app.view = back;
             Action::None
         }
         KeyCode::Enter => {
-            if let Some(email) = app.selected_email() {
-                let uid = email.uid;
-                let duration_str = app.remind.input.trim().to_string();
-                app.remind = Default::default();
-                app.view = View::Inbox;
-                Action::RemindEmail(uid, duration_str)
-            } else {
-                Action::None
+            let when = app.remind.input.trim().to_string();
+            match app.remind.target {
+                RemindTarget::Reschedule(id) => {
+                    app.remind = Default::default();
+                    app.view = back;
+                    Action::RescheduleReminder(id, when)
+                }
+                RemindTarget::FollowUp => {
+                    app.remind = Default::default();
+                    app.view = back;
+                    Action::FollowUp(when)
+                }
+                RemindTarget::SendLater => {
+                    // Compose is left once the message is queued
+                    app.remind = Default::default();
+                    app.view = back;
+                    Action::SendLater(when)
+                }
+                RemindTarget::RescheduleSend(id) => {
+                    app.remind = Default::default();
+                    app.view = back;
+                    Action::RescheduleQueued(id, when)
+                }
+                RemindTarget::Selected => match app.selected_email() {
+                    Some(email) => {
+                        let key = email.key();
+                        app.remind = Default::default();
+                        app.view = back;
+                        Action::RemindEmail(key, when)
+                    }
+                    None => Action::None,
+                },
             }
         }
         KeyCode::Backspace => {
@@ -12,7 +12,6 @@ pub struct Theme {
     pub error: Color,
     pub selection: Color,
     pub border: Color,
-    pub unread: Color,
 }
 
 impl Default for Theme {
@@ -29,7 +28,6 @@ impl Default for Theme {
             error: Color::Rgb(229, 115, 115),     // Soft red
             selection: Color::Rgb(45, 45, 50),
             border: Color::Rgb(60, 60, 65),
-            unread: Color::Rgb(138, 180, 248),
         }
     }
 }
@@ -36,17 +36,3 @@ pub fn truncate(s: &str, max_len: usize) -> String {
         format!("{}…", truncated)
     }
 }
-
-pub fn format_email_preview(body: &str, max_len: usize) -> String {
-    let preview: String = body
-        .lines()
-        .filter(|l| !l.trim().is_empty())
-        .take(3)
-        .collect::<Vec<_>>()
-        .join(" ")
-        .chars()
-        .filter(|c| !c.is_control())
-        .collect();
-    
-    truncate(&preview, max_len)
-}
@@ -0,0 +1,619 @@
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::auth::GoogleAuth;
+use crate::cache::FolderCache;
+use crate::config::{AccountConfig, AuthMethod};
+use crate::email::{self, Email, FolderInfo, ImapClient, MessageRef, OutgoingEmail, ReminderOutcome, SpecialUse};
+
+/// Work handed to an account's background thread
+pub enum Request {
+    FetchFolder { folder: String },
+    /// A special-use folder the UI has no name for yet, fetched and
+    /// watched like `FetchFolder` once the server says which it is
+    FetchSpecial { special: SpecialUse },
+    /// Every folder with its unread count, for the sidebar
+    ListFolders,
+    /// A page of a server search, the first one at `start` 0
+    Search { query: String, start: u32 },
+    /// The page after the first `start` messages
+    FetchMore { folder: String, start: u32 },
+    /// A page of the flagged messages in INBOX, which stand in for Starred
+    /// on servers without a \Flagged folder
+    FetchFlagged { start: u32 },
+    FetchBody { folder: String, uid: u32, section: Option<String> },
+    Archive { targets: Vec<(String, u32)> },
+    Delete { targets: Vec<(String, u32)> },
+    Move { targets: Vec<(String, u32)>, to: String },
+    /// Add or remove a Gmail label
+    Label { targets: Vec<(String, u32)>, label: String, add: bool },
+    /// Undo an archive or delete: put messages back into `folder`, found
+    /// by Message-ID and Gmail's message ID
+    Restore { folder: String, messages: Vec<MessageRef> },
+    MarkAsRead { folder: String, uid: u32 },
+    Star { folder: String, uid: u32, flagged: bool },
+    FetchThread { email: Email },
+    /// `queued` names the outbox entry being sent, if any
+    Send { email: OutgoingEmail, queued: Option<u64> },
+    SaveDraft { email: OutgoingEmail },
+    /// Move a snoozed message back to the inbox, `id` names the reminder.
+    /// With `follow_up`, only if nobody has replied to it.
+    Resurface { id: u64, message_id: Option<String>, gmail_id: Option<u64>, follow_up: bool },
+}
+
+/// Which messages an operation on several got done, by UID, and why the
+/// others failed
+#[derive(Debug, Default)]
+pub struct BatchResult {
+    pub done: Vec<(String, u32)>,
+    pub failed: Vec<(String, u32, String)>,
+}
+
+/// Result of a `Request`, sent back to the UI thread
+pub enum Response {
+    /// What the disk cache has for a folder, ahead of the server's answer
+    Cached { folder: String, emails: Vec<Email> },
+    Emails { folder: String, emails: Vec<Email> },
+    /// Every folder, and whether the account is Gmail's with labels
+    Folders { folders: Vec<FolderInfo>, gmail: bool },
+    /// Messages matching a search, a page below those listed unless `start` is 0
+    Found { query: String, start: u32, emails: Vec<Email> },
+    /// Flagged messages of INBOX, listed as Starred, a page below those
+    /// listed unless `start` is 0
+    Flagged { start: u32, emails: Vec<Email> },
+    /// An older page, to go below what is already listed
+    MoreEmails { folder: String, emails: Vec<Email> },
+    MoreFailed(String),
+    Body { folder: String, uid: u32, body: String },
+    BodyFailed { folder: String, uid: u32, error: String },
+    /// Pushed by the watcher, not an answer to any request
+    NewEmails { folder: String, emails: Vec<Email> },
+    /// The watcher lost its connection, also unasked for
+    WatchFailed(String),
+    /// A new OAuth access token that should be saved to the config
+    TokenRefreshed(String),
+    Archived(BatchResult),
+    Deleted(BatchResult),
+    Moved(BatchResult),
+    Labeled { label: String, add: bool, result: BatchResult },
+    Restored { folder: String, restored: usize, total: usize },
+    MarkedAsRead,
+    Starred,
+    /// The star is still as it was before `flagged` was asked for
+    StarFailed { folder: String, uid: u32, flagged: bool, error: String },
+    Thread(Vec<Email>),
+    /// `email` with the Message-ID it went out with
+    Sent { email: OutgoingEmail, queued: Option<u64> },
+    SendFailed { email: OutgoingEmail, error: String, queued: Option<u64> },
+    DraftSaved,
+    DraftFailed { email: OutgoingEmail, error: String },
+    Resurfaced { id: u64, outcome: ReminderOutcome },
+    /// Could not get to the server, worth another try later
+    ResurfaceFailed { id: u64, error: String },
+    Error(String),
+}
+
+impl Response {
+    /// Whether this is the final answer to a request, as opposed to an
+    /// early or unsolicited update
+    pub fn completes_task(&self) -> bool {
+        !matches!(
+            self,
+            Response::Cached { .. }
+                | Response::NewEmails { .. }
+                | Response::WatchFailed(_)
+                | Response::TokenRefreshed(_)
+        )
+    }
+}
+
+pub struct WorkerEvent {
+    pub account: usize,
+    pub response: Response,
+}
+
+/// How long one IDLE round lasts before the watcher checks for a new folder
+const IDLE_INTERVAL: Duration = Duration::from_secs(30);
+/// How often servers without IDLE are polled
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+/// Wait before reconnecting a watcher that lost its connection
+const RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+pub struct Worker {
+    requests: Sender<Request>,
+    folders: Sender<String>,
+}
+
+impl Worker {
+    /// Start the account's threads. Nothing connects until there is work,
+    /// so this returns immediately even when offline.
+    pub fn spawn(
+        account: usize,
+        config: AccountConfig,
+        page_size: u32,
+        events: Sender<WorkerEvent>,
+    ) -> Self {
+        let (requests, inbox): (Sender<Request>, Receiver<Request>) = mpsc::channel();
+        let (folders, changes) = mpsc::channel();
+        let config = Arc::new(Mutex::new(config));
+
+        let watch_config = Arc::clone(&config);
+        let watch_events = events.clone();
+        let watched = folders.clone();
+        thread::spawn(move || watch(account, &watch_config, &changes, &watch_events));
+
+        thread::spawn(move || {
+            let mut session = AccountSession {
+                account,
+                config,
+                page_size,
+                events,
+                watched,
+                client: None,
+                caches: HashMap::new(),
+            };
+            // Runs until the UI drops its end of the channel
+            while let Ok(request) = inbox.recv() {
+                let response = session.handle(request);
+                if !session.emit(response) {
+                    break;
+                }
+            }
+            if let Some(client) = session.client.take() {
+                let _ = client.logout();
+            }
+        });
+
+        Self { requests, folders }
+    }
+
+    pub fn send(&self, request: Request) {
+        let _ = self.requests.send(request);
+    }
+
+    /// Point the new mail watcher at `folder`
+    pub fn watch(&self, folder: &str) {
+        let _ = self.folders.send(folder.to_string());
+    }
+}
+
+/// Connect, refreshing an expired OAuth access token once. A refreshed
+/// token is handed to the UI so it ends up in the config file.
+fn connect(
+    account: usize,
+    config: &Mutex<AccountConfig>,
+    events: &Sender<WorkerEvent>,
+) -> Result<ImapClient> {
+    let mut snapshot = config.lock().unwrap().clone();
+    match ImapClient::connect(&snapshot) {
+        Ok(client) => Ok(client),
+        Err(e) if snapshot.auth != AuthMethod::Xoauth2 => Err(e),
+        Err(e) => {
+            let Some(refresh_token) = snapshot.refresh_token.clone() else {
+                return Err(e);
+            };
+            let access_token = GoogleAuth::new(&snapshot)?.refresh_token(&refresh_token)?;
+            config.lock().unwrap().access_token = Some(access_token.clone());
+            let _ = events.send(WorkerEvent {
+                account,
+                response: Response::TokenRefreshed(access_token.clone()),
+            });
+            snapshot.access_token = Some(access_token);
+            ImapClient::connect(&snapshot)
+        }
+    }
+}
+
+/// The request side of an account: one IMAP connection, opened on demand,
+/// and the disk caches of the folders visited so far
+struct AccountSession {
+    account: usize,
+    config: Arc<Mutex<AccountConfig>>,
+    page_size: u32,
+    events: Sender<WorkerEvent>,
+    /// Points the watcher at a folder, for those resolved here
+    watched: Sender<String>,
+    client: Option<ImapClient>,
+    caches: HashMap<String, FolderCache>,
+}
+
+impl AccountSession {
+    /// Returns false once the UI has gone away
+    fn emit(&self, response: Response) -> bool {
+        self.events
+            .send(WorkerEvent {
+                account: self.account,
+                response,
+            })
+            .is_ok()
+    }
+
+    fn cache(&mut self, folder: &str) -> &mut FolderCache {
+        let email = self.config.lock().unwrap().email.clone();
+        self.caches
+            .entry(folder.to_string())
+            .or_insert_with(|| FolderCache::load(&email, folder))
+    }
+
+    /// Run `op` on the connection, connecting first if needed. A connection
+    /// that broke is dropped so the next request starts a fresh one.
+    fn with_client<T>(
+        &mut self,
+        op: impl FnOnce(&mut ImapClient, &mut HashMap<String, FolderCache>) -> Result<T>,
+    ) -> Result<T> {
+        if self.client.is_none() {
+            self.client = Some(connect(self.account, &self.config, &self.events)?);
+        }
+        let client = self.client.as_mut().expect("connected above");
+        let result = op(client, &mut self.caches);
+        if let Err(e) = &result {
+            if email::is_disconnect(e) {
+                self.client = None;
+            }
+        }
+        result
+    }
+
+    fn tag(&self, mut emails: Vec<Email>) -> Vec<Email> {
+        for email in &mut emails {
+            email.account = self.account;
+        }
+        emails
+    }
+
+    /// The first page of `folder`, with what the cache has sent ahead
+    fn fetch_folder(&mut self, folder: String) -> Response {
+        let page_size = self.page_size;
+        let cached = self.cache(&folder).newest(page_size as usize);
+        if !cached.is_empty() {
+            let emails = self.tag(cached);
+            self.emit(Response::Cached {
+                folder: folder.clone(),
+                emails,
+            });
+        }
+
+        let result = self.with_client(|client, caches| {
+            let cache = caches.get_mut(&folder).expect("loaded above");
+            let emails = client.fetch_emails(&folder, 0, page_size, cache)?;
+            cache.save()?;
+            Ok(emails)
+        });
+        match result {
+            Ok(emails) => Response::Emails {
+                emails: self.tag(emails),
+                folder,
+            },
+            Err(e) => Response::Error(format!("Error: {}", e)),
+        }
+    }
+
+    fn fetch_flagged(&mut self, start: u32) -> Response {
+        let page_size = self.page_size;
+        match self.with_client(|client, _| client.flagged(start, page_size)) {
+            Ok(emails) => Response::Flagged {
+                emails: self.tag(emails),
+                start,
+            },
+            Err(e) if start > 0 => Response::MoreFailed(format!("Error: {}", e)),
+            Err(e) => Response::Error(format!("Error: {}", e)),
+        }
+    }
+
+    fn handle(&mut self, request: Request) -> Response {
+        match request {
+            Request::FetchFolder { folder } => self.fetch_folder(folder),
+            Request::FetchSpecial { special } => {
+                match self.with_client(|client, _| client.special_folder(special)) {
+                    Ok(folder) => {
+                        let _ = self.watched.send(folder.clone());
+                        self.fetch_folder(folder)
+                    }
+                    Err(_) if special == SpecialUse::Flagged => self.fetch_flagged(0),
+                    Err(e) => Response::Error(format!("Error: {}", e)),
+                }
+            }
+            Request::FetchFlagged { start } => self.fetch_flagged(start),
+            Request::FetchMore { folder, start } => {
+                let page_size = self.page_size;
+                self.cache(&folder);
+                let result = self.with_client(|client, caches| {
+                    let cache = caches.get_mut(&folder).expect("loaded above");
+                    let emails = client.fetch_emails(&folder, start, page_size, cache)?;
+                    cache.save()?;
+                    Ok(emails)
+                });
+                match result {
+                    Ok(emails) => Response::MoreEmails {
+                        emails: self.tag(emails),
+                        folder,
+                    },
+                    Err(e) => Response::MoreFailed(format!("Error: {}", e)),
+                }
+            }
+            Request::FetchBody { folder, uid, section } => {
+                let result = self.with_client(|client, _| {
+                    client.fetch_body(&folder, uid, section.as_deref())
+                });
+                match result {
+                    Ok(body) => {
+                        let cache = self.cache(&folder);
+                        if let Some(email) = cache.get_mut(uid) {
+                            email.body = body.clone();
+                            email.body_loaded = true;
+                            let _ = cache.save();
+                        }
+                        Response::Body { folder, uid, body }
+                    }
+                    Err(e) => Response::BodyFailed {
+                        folder,
+                        uid,
+                        error: e.to_string(),
+                    },
+                }
+            }
+            Request::Archive { targets } => {
+                Response::Archived(self.for_each_folder(targets, true, |client, folder, uids| {
+                    client.archive_emails(folder, uids)
+                }))
+            }
+            Request::Delete { targets } => {
+                Response::Deleted(self.for_each_folder(targets, true, |client, folder, uids| {
+                    client.delete_emails(folder, uids)
+                }))
+            }
+            Request::Move { targets, to } => {
+                Response::Moved(self.for_each_folder(targets, true, |client, folder, uids| {
+                    client.move_emails(folder, uids, &to)
+                }))
+            }
+            Request::Label { targets, label, add } => {
+                let result = self.for_each_folder(targets, false, |client, folder, uids| {
+                    client.set_label(folder, uids, &label, add)
+                });
+                Response::Labeled { label, add, result }
+            }
+            Request::Search { query, start } => {
+                let page_size = self.page_size;
+                match self.with_client(|client, _| client.search(&query, start, page_size)) {
+                    Ok(emails) => Response::Found {
+                        emails: self.tag(emails),
+                        query,
+                        start,
+                    },
+                    Err(e) if start > 0 => Response::MoreFailed(format!("Search failed: {}", e)),
+                    Err(e) => Response::Error(format!("Search failed: {}", e)),
+                }
+            }
+            Request::ListFolders => {
+                let result = self.with_client(|client, _| Ok((client.list_folders()?, client.is_gmail())));
+                match result {
+                    Ok((folders, gmail)) => Response::Folders { folders, gmail },
+                    Err(e) => Response::Error(format!("Could not list folders: {}", e)),
+                }
+            }
+            Request::Restore { folder, messages } => {
+                let total = messages.len();
+                match self.with_client(|client, _| client.restore(&folder, &messages)) {
+                    Ok(restored) => Response::Restored { folder, restored, total },
+                    Err(e) => Response::Error(format!("Undo failed: {}", e)),
+                }
+            }
+            Request::MarkAsRead { folder, uid } => {
+                match self.with_client(|client, _| client.mark_as_read(&folder, uid)) {
+                    Ok(_) => {
+                        let cache = self.cache(&folder);
+                        if let Some(email) = cache.get_mut(uid) {
+                            email.seen = true;
+                            let _ = cache.save();
+                        }
+                        Response::MarkedAsRead
+                    }
+                    Err(e) => Response::Error(format!("Mark as read failed: {}", e)),
+                }
+            }
+            Request::Star { folder, uid, flagged } => {
+                match self.with_client(|client, _| client.set_flagged(&folder, uid, flagged)) {
+                    Ok(_) => {
+                        let cache = self.cache(&folder);
+                        if let Some(email) = cache.get_mut(uid) {
+                            email.flagged = flagged;
+                            let _ = cache.save();
+                        }
+                        Response::Starred
+                    }
+                    Err(e) => Response::StarFailed {
+                        folder,
+                        uid,
+                        flagged,
+                        error: e.to_string(),
+                    },
+                }
+            }
+            Request::FetchThread { email } => {
+                match self.with_client(|client, _| client.fetch_thread(&email)) {
+                    Ok(thread) => Response::Thread(thread),
+                    Err(e) => Response::Error(format!("Thread fetch failed: {}", e)),
+                }
+            }
+            Request::Send { email, queued } => {
+                let config = self.config.lock().unwrap().clone();
+                match email::send_email(&config, &email) {
+                    Ok(message_id) => Response::Sent {
+                        email: OutgoingEmail {
+                            message_id: Some(message_id),
+                            ..email
+                        },
+                        queued,
+                    },
+                    Err(e) => Response::SendFailed {
+                        email,
+                        error: e.to_string(),
+                        queued,
+                    },
+                }
+            }
+            Request::SaveDraft { email } => {
+                let from = self.config.lock().unwrap().email.clone();
+                match self.with_client(|client, _| client.save_draft(&from, &email)) {
+                    Ok(_) => Response::DraftSaved,
+                    Err(e) => Response::DraftFailed {
+                        email,
+                        error: e.to_string(),
+                    },
+                }
+            }
+            Request::Resurface { id, message_id, gmail_id, follow_up } => {
+                let me = self.config.lock().unwrap().email.clone();
+                let result = self.with_client(|client, _| match message_id.as_deref() {
+                    Some(message_id) if follow_up => client.follow_up(message_id, &me),
+                    _ => client.resurface(message_id.as_deref(), gmail_id),
+                });
+                match result {
+                    Ok(outcome) => Response::Resurfaced { id, outcome },
+                    Err(e) => Response::ResurfaceFailed {
+                        id,
+                        error: e.to_string(),
+                    },
+                }
+            }
+        }
+    }
+
+    /// Run `op` once per folder over all of that folder's targets. When
+    /// it fails for more than one message, each is tried on its own to
+    /// find out which cannot be done. With `moves_out` the messages done
+    /// are dropped from the folder's cache.
+    fn for_each_folder(
+        &mut self,
+        targets: Vec<(String, u32)>,
+        moves_out: bool,
+        op: impl Fn(&mut ImapClient, &str, &[u32]) -> Result<()>,
+    ) -> BatchResult {
+        let mut by_folder: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+        for (folder, uid) in targets {
+            by_folder.entry(folder).or_default().push(uid);
+        }
+
+        let mut result = BatchResult::default();
+        for (folder, uids) in by_folder {
+            let done = match self.with_client(|client, _| op(client, &folder, &uids)) {
+                Ok(()) => uids,
+                Err(e) if uids.len() == 1 => {
+                    result.failed.push((folder.clone(), uids[0], e.to_string()));
+                    Vec::new()
+                }
+                Err(_) => {
+                    let mut done = Vec::new();
+                    for uid in uids {
+                        match self.with_client(|client, _| op(client, &folder, &[uid])) {
+                            Ok(()) => done.push(uid),
+                            Err(e) => result.failed.push((folder.clone(), uid, e.to_string())),
+                        }
+                    }
+                    done
+                }
+            };
+            if moves_out {
+                let cache = self.cache(&folder);
+                for &uid in &done {
+                    cache.remove(uid);
+                }
+                let _ = cache.save();
+            }
+            result.done.extend(done.into_iter().map(|uid| (folder.clone(), uid)));
+        }
+        result
+    }
+}
+
+/// Watch a folder for new mail over a second connection, so IDLE never
+/// holds up requests. Runs until the UI goes away.
+fn watch(
+    account: usize,
+    config: &Mutex<AccountConfig>,
+    changes: &Receiver<String>,
+    events: &Sender<WorkerEvent>,
+) {
+    let Ok(mut folder) = changes.recv() else {
+        return;
+    };
+    let mut failing = false;
+    loop {
+        match watch_folder(account, config, &mut folder, &mut failing, changes, events) {
+            Ok(()) => return,
+            Err(e) => {
+                // Report once, then keep retrying quietly
+                if !failing {
+                    let response = Response::WatchFailed(format!("New mail watcher: {}", e));
+                    if events.send(WorkerEvent { account, response }).is_err() {
+                        return;
+                    }
+                    failing = true;
+                }
+                thread::sleep(RECONNECT_DELAY);
+            }
+        }
+    }
+}
+
+/// Returns Ok once the UI has gone away, Err when the connection broke
+fn watch_folder(
+    account: usize,
+    config: &Mutex<AccountConfig>,
+    folder: &mut String,
+    failing: &mut bool,
+    changes: &Receiver<String>,
+    events: &Sender<WorkerEvent>,
+) -> Result<()> {
+    let mut client = connect(account, config, events)?;
+    let idle = client.supports_idle();
+    let mut last_uid = client.last_uid(folder)?;
+    *failing = false;
+
+    loop {
+        if idle {
+            client.idle(IDLE_INTERVAL)?;
+        } else {
+            thread::sleep(POLL_INTERVAL);
+            client.noop()?;
+        }
+
+        // Only the most recent folder matters
+        let mut next = None;
+        loop {
+            match changes.try_recv() {
+                Ok(f) => next = Some(f),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+        }
+        if let Some(next) = next {
+            if next != *folder {
+                *folder = next;
+                last_uid = client.last_uid(folder)?;
+                continue;
+            }
+        }
+
+        let mut emails = client.fetch_new(folder, last_uid)?;
+        if emails.is_empty() {
+            continue;
+        }
+        for email in &mut emails {
+            email.account = account;
+            last_uid = last_uid.max(email.uid);
+        }
+        let response = Response::NewEmails {
+            folder: folder.clone(),
+            emails,
+        };
+        if events.send(WorkerEvent { account, response }).is_err() {
+            return Ok(());
+        }
+    }
+}